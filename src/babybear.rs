@@ -0,0 +1,55 @@
+use crate::treepp::*;
+use crate::two_adic31;
+
+pub(crate) const BABYBEAR_MOD: u32 = 15 * (1 << 27) + 1;
+
+pub fn babybear_add() -> Script {
+    two_adic31::add(BABYBEAR_MOD)
+}
+
+pub fn babybear_double() -> Script {
+    two_adic31::double(BABYBEAR_MOD)
+}
+
+pub fn babybear_sub() -> Script {
+    two_adic31::sub(BABYBEAR_MOD)
+}
+
+pub fn babybear_neg() -> Script {
+    two_adic31::neg(BABYBEAR_MOD)
+}
+
+pub fn babybear_mul() -> Script {
+    two_adic31::mul(BABYBEAR_MOD)
+}
+
+pub fn babybear_mul_by_small_constant(c: u32) -> Script {
+    two_adic31::mul_by_small_constant(BABYBEAR_MOD, c)
+}
+
+pub fn babybear_inverse() -> Script {
+    // compute a^(p - 2) where p - 2 = 0b1110 followed by 27 ones, with a window of three bits
+    //
+    // a^(2^3 - 1) = (a^2 * a)^2 * a
+    // a^(0b1110) = (a^(2^3 - 1))^2
+    // then, 9 times, shift in three ones: t = t^(2^3) * a^(2^3 - 1)
+    //
+    // note: zero is mapped to zero
+    script! {
+        OP_DUP OP_DUP babybear_mul
+        OP_OVER babybear_mul
+        OP_DUP babybear_mul
+        OP_SWAP babybear_mul
+        // current stack: a^7
+
+        OP_DUP
+        OP_DUP babybear_mul
+        for _ in 0..9 {
+            for _ in 0..3 {
+                OP_DUP babybear_mul
+            }
+            OP_OVER babybear_mul
+        }
+        OP_NIP
+    }
+}
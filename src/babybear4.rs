@@ -0,0 +1,56 @@
+use crate::babybear::{babybear_inverse, BABYBEAR_MOD};
+use crate::treepp::*;
+use crate::two_adic31;
+
+// An element a0 + a1 * x + a2 * x^2 + a3 * x^3 of the extension BabyBear[x] / (x^4 - 11)
+// is kept on the stack as
+//
+//      a3 a2 a1 a0
+//
+// with a0 on top, in the same way as qm31.
+
+pub(crate) const BABYBEAR4_NONRESIDUE: u32 = 11;
+
+pub fn babybear4_add() -> Script {
+    two_adic31::ext4_add(BABYBEAR_MOD)
+}
+
+pub fn babybear4_sub() -> Script {
+    two_adic31::ext4_sub(BABYBEAR_MOD)
+}
+
+pub fn babybear4_equalverify() -> Script {
+    two_adic31::ext4_equalverify()
+}
+
+pub fn babybear4_mul() -> Script {
+    two_adic31::ext4_mul(BABYBEAR_MOD, BABYBEAR4_NONRESIDUE)
+}
+
+pub fn babybear4_square() -> Script {
+    two_adic31::ext4_square(BABYBEAR_MOD, BABYBEAR4_NONRESIDUE)
+}
+
+pub fn babybear4_mul_babybear() -> Script {
+    two_adic31::ext4_mul_base(BABYBEAR_MOD)
+}
+
+pub fn babybear4_inverse() -> Script {
+    two_adic31::ext4_inverse(BABYBEAR_MOD, BABYBEAR4_NONRESIDUE, babybear_inverse())
+}
+
+pub fn babybear4_toaltstack() -> Script {
+    two_adic31::ext4_toaltstack()
+}
+
+pub fn babybear4_fromaltstack() -> Script {
+    two_adic31::ext4_fromaltstack()
+}
+
+pub fn babybear4_copy(offset: usize) -> Script {
+    two_adic31::ext4_copy(offset)
+}
+
+pub fn babybear4_roll(offset: usize) -> Script {
+    two_adic31::ext4_roll(offset)
+}
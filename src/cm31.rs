@@ -0,0 +1,755 @@
+use crate::karatsuba_complex::karatsuba_small;
+use crate::m31::M31;
+use crate::m31::{
+    m31_add, m31_double, m31_inverse, m31_is_square, m31_mul, m31_mul_by_constant, m31_pow_const,
+    m31_sqrt, m31_square, m31_sub, MOD,
+};
+use crate::treepp::pushable::{Builder, Pushable};
+use crate::treepp::*;
+use core::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+use p3_field::extension::Complex;
+use p3_mersenne_31::Mersenne31 as P3M31;
+use rand::distributions::{Distribution, Standard};
+
+// A cm31 element a + b * i is kept on the stack as
+//
+//      b a
+//
+// that is, the imaginary part first and the real part on top.
+
+/// An element a + b * i of the complex extension CM31 = M31[i] / (i^2 + 1), stored as
+/// `CM31(a, b)`.
+///
+/// It is pushed to the stack as `b a`, the imaginary part first and the real part on top.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CM31(pub M31, pub M31);
+
+impl CM31 {
+    pub const ZERO: CM31 = CM31(M31::ZERO, M31::ZERO);
+    pub const ONE: CM31 = CM31(M31::ONE, M31::ZERO);
+
+    pub fn new(real: u32, imag: u32) -> CM31 {
+        CM31(M31::new(real), M31::new(imag))
+    }
+
+    pub fn real(&self) -> M31 {
+        self.0
+    }
+
+    pub fn imag(&self) -> M31 {
+        self.1
+    }
+
+    /// The limbs in the stack order, i.e., `[imag, real]`, as expected by
+    /// `cm31_mul_by_constant`.
+    pub fn limbs(&self) -> [u32; 2] {
+        [self.1.value(), self.0.value()]
+    }
+
+    /// The inverse of `limbs`.
+    pub fn from_limbs(limbs: [u32; 2]) -> CM31 {
+        CM31::new(limbs[1], limbs[0])
+    }
+
+    pub fn double(&self) -> CM31 {
+        *self + *self
+    }
+
+    pub fn square(&self) -> CM31 {
+        *self * *self
+    }
+
+    pub fn conjugate(&self) -> CM31 {
+        CM31(self.0, -self.1)
+    }
+
+    pub fn norm(&self) -> M31 {
+        self.0.square() + self.1.square()
+    }
+
+    pub fn pow(&self, mut e: u128) -> CM31 {
+        let mut res = CM31::ONE;
+        let mut base = *self;
+        while e > 0 {
+            if e & 1 == 1 {
+                res *= base;
+            }
+            base = base.square();
+            e >>= 1;
+        }
+        res
+    }
+
+    /// The inverse, where zero is mapped to zero as in `cm31_inverse`.
+    pub fn inverse(&self) -> CM31 {
+        let norm_inv = self.norm().inverse();
+        CM31(self.0 * norm_inv, -self.1 * norm_inv)
+    }
+}
+
+impl From<M31> for CM31 {
+    fn from(value: M31) -> CM31 {
+        CM31(value, M31::ZERO)
+    }
+}
+
+impl From<Complex<P3M31>> for CM31 {
+    fn from(value: Complex<P3M31>) -> CM31 {
+        CM31(value.real().into(), value.imag().into())
+    }
+}
+
+impl Add for CM31 {
+    type Output = CM31;
+
+    fn add(self, rhs: CM31) -> CM31 {
+        CM31(self.0 + rhs.0, self.1 + rhs.1)
+    }
+}
+
+impl Sub for CM31 {
+    type Output = CM31;
+
+    fn sub(self, rhs: CM31) -> CM31 {
+        CM31(self.0 - rhs.0, self.1 - rhs.1)
+    }
+}
+
+impl Neg for CM31 {
+    type Output = CM31;
+
+    fn neg(self) -> CM31 {
+        CM31(-self.0, -self.1)
+    }
+}
+
+impl Mul for CM31 {
+    type Output = CM31;
+
+    fn mul(self, rhs: CM31) -> CM31 {
+        CM31(
+            self.0 * rhs.0 - self.1 * rhs.1,
+            self.0 * rhs.1 + self.1 * rhs.0,
+        )
+    }
+}
+
+impl Div for CM31 {
+    type Output = CM31;
+
+    fn div(self, rhs: CM31) -> CM31 {
+        self * rhs.inverse()
+    }
+}
+
+impl AddAssign for CM31 {
+    fn add_assign(&mut self, rhs: CM31) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for CM31 {
+    fn sub_assign(&mut self, rhs: CM31) {
+        *self = *self - rhs;
+    }
+}
+
+impl MulAssign for CM31 {
+    fn mul_assign(&mut self, rhs: CM31) {
+        *self = *self * rhs;
+    }
+}
+
+impl Distribution<CM31> for Standard {
+    fn sample<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> CM31 {
+        CM31(rng.gen(), rng.gen())
+    }
+}
+
+impl Pushable for CM31 {
+    fn bitcoin_script_push(self, builder: Builder) -> Builder {
+        let builder = self.1.bitcoin_script_push(builder);
+        self.0.bitcoin_script_push(builder)
+    }
+}
+
+pub fn cm31_add() -> Script {
+    script! {
+        OP_ROT
+        m31_add
+        OP_TOALTSTACK
+        m31_add
+        OP_FROMALTSTACK
+    }
+}
+
+pub fn cm31_equalverify() -> Script {
+    script! {
+        OP_ROT
+        OP_EQUALVERIFY
+        OP_EQUALVERIFY
+    }
+}
+
+pub fn cm31_sub() -> Script {
+    script! {
+        OP_ROT OP_SWAP
+        m31_sub
+        OP_TOALTSTACK
+        m31_sub
+        OP_FROMALTSTACK
+    }
+}
+
+pub fn cm31_neg() -> Script {
+    script! {
+        0 OP_SWAP m31_sub
+        OP_SWAP
+        0 OP_SWAP m31_sub
+        OP_SWAP
+    }
+}
+
+pub fn cm31_double() -> Script {
+    script! {
+        m31_double
+        OP_SWAP
+        m31_double
+        OP_SWAP
+    }
+}
+
+pub fn cm31_mul() -> Script {
+    script! {
+        karatsuba_small
+    }
+}
+
+pub fn cm31_square() -> Script {
+    // (a + b * i)^2 = (a + b) * (a - b) + 2 * a * b * i
+    script! {
+        OP_2DUP m31_mul m31_double
+        OP_ROT OP_ROT
+        OP_2DUP m31_add
+        OP_ROT OP_ROT
+        OP_SWAP m31_sub
+        m31_mul
+    }
+}
+
+pub fn cm31_conjugate() -> Script {
+    script! {
+        OP_SWAP
+        0 OP_SWAP m31_sub
+        OP_SWAP
+    }
+}
+
+pub fn cm31_norm() -> Script {
+    // input stack: b a
+    // output stack: a^2 + b^2
+    script! {
+        m31_square
+        OP_SWAP
+        m31_square
+        m31_add
+    }
+}
+
+pub fn cm31_inverse() -> Script {
+    // (a + b * i)^-1 = (a - b * i) / (a^2 + b^2)
+    //
+    // note: zero is mapped to zero
+    script! {
+        OP_2DUP cm31_norm
+        m31_inverse
+        OP_TUCK m31_mul
+        OP_TOALTSTACK
+        m31_mul
+        0 OP_SWAP m31_sub
+        OP_FROMALTSTACK
+    }
+}
+
+pub fn cm31_is_square() -> Script {
+    // input stack: b a
+    // output stack: 1 if a + b * i is a square (including 0), 0 otherwise
+    //
+    // a + b * i is a square if and only if its norm a^2 + b^2 is a square in m31
+    script! {
+        cm31_norm
+        m31_is_square
+    }
+}
+
+pub fn cm31_sqrt() -> Script {
+    // input stack: b a
+    // output stack: y x such that (x + y * i)^2 = a + b * i
+    //
+    // idea:
+    // - n = sqrt(a^2 + b^2) is computed with m31_sqrt, which fails if a + b * i is not a square
+    // - x^2 = t1 = (a + n) / 2 and y^2 = t2 = (n - a) / 2, with t1 * t2 = b^2 / 4
+    // - with r1 = t1^((p + 1) / 4) and r2 = t2^((p + 1) / 4), either r1^2 = t1 and then
+    //   r2^2 = t2, or both t1 and t2 are not squares and then r2^2 = -t2 and r1^2 = -t1 are
+    //   the right values for x^2 and y^2
+    // - the sign of y is fixed so that 2 * x * y = b
+    let half = (MOD + 1) / 2;
+
+    script! {
+        OP_2DUP cm31_norm
+        m31_sqrt
+        // current stack: b a n
+
+        OP_2DUP m31_add
+        OP_ROT OP_ROT
+        OP_SWAP m31_sub
+        { m31_mul_by_constant(half) }
+        OP_SWAP
+        { m31_mul_by_constant(half) }
+        OP_SWAP
+        // current stack: b t1 t2
+
+        { m31_pow_const((MOD + 1) / 4) }
+        OP_SWAP OP_DUP
+        { m31_pow_const((MOD + 1) / 4) }
+        OP_TUCK m31_square OP_EQUAL
+        OP_NOTIF OP_SWAP OP_ENDIF
+        // current stack: b y x
+
+        OP_2DUP m31_mul m31_double
+        3 OP_ROLL OP_EQUAL
+        OP_NOTIF
+            OP_SWAP
+            0 OP_SWAP m31_sub
+            OP_SWAP
+        OP_ENDIF
+    }
+}
+
+pub fn cm31_sqrt_with_hint() -> Script {
+    // input stack: z r
+    // where r is provided by the prover as a hint
+    //
+    // the script checks that r is a valid cm31 element and that r^2 = z,
+    // both r and -r are accepted
+    //
+    // output stack: r
+    script! {
+        for i in 0..2 {
+            { i } OP_PICK
+            0 { MOD } OP_WITHIN OP_VERIFY
+        }
+        OP_2DUP cm31_square
+        { cm31_roll(2) }
+        cm31_equalverify
+    }
+}
+
+pub fn cm31_mul_m31() -> Script {
+    // input stack: b a e
+    // output stack: b * e, a * e
+    script! {
+        OP_TUCK m31_mul
+        OP_TOALTSTACK
+        m31_mul
+        OP_FROMALTSTACK
+    }
+}
+
+// input stack: x y
+// output stack: cx * x + cy * y
+//
+// a negative coefficient is often cheaper to apply through m31_sub, so all the sign choices
+// are tried and the shortest script is kept
+fn m31_lincomb2(cx: u32, cy: u32) -> Script {
+    let cx = cx % MOD;
+    let cy = cy % MOD;
+
+    if cx == 0 && cy == 0 {
+        return script! {
+            OP_2DROP 0
+        };
+    }
+    if cy == 0 {
+        return script! {
+            OP_DROP { m31_mul_by_constant(cx) }
+        };
+    }
+    if cx == 0 {
+        return script! {
+            OP_NIP { m31_mul_by_constant(cy) }
+        };
+    }
+
+    let candidates = [
+        script! {
+            { m31_mul_by_constant(cy) }
+            OP_SWAP { m31_mul_by_constant(cx) }
+            m31_add
+        },
+        script! {
+            OP_SWAP { m31_mul_by_constant(cx) }
+            OP_SWAP { m31_mul_by_constant(MOD - cy) }
+            m31_sub
+        },
+        script! {
+            { m31_mul_by_constant(cy) }
+            OP_SWAP { m31_mul_by_constant(MOD - cx) }
+            m31_sub
+        },
+        script! {
+            { m31_mul_by_constant(MOD - cy) }
+            OP_SWAP { m31_mul_by_constant(MOD - cx) }
+            m31_add
+            0 OP_SWAP m31_sub
+        },
+    ];
+
+    candidates.into_iter().min_by_key(|s| s.len()).unwrap()
+}
+
+pub fn cm31_mul_by_constant(c: [u32; 2]) -> Script {
+    // input stack: b a
+    // c is given in the stack order, i.e., c = [q, p] for p + q * i
+    //
+    // (a + b * i) * (p + q * i) = (a * p - b * q) + (a * q + b * p) * i
+    let [q, p] = c;
+    let q = q % MOD;
+    let p = p % MOD;
+
+    if q == 0 && p == 1 {
+        return script! {};
+    }
+    if q == 0 {
+        return script! {
+            { m31_mul_by_constant(p) }
+            OP_SWAP
+            { m31_mul_by_constant(p) }
+            OP_SWAP
+        };
+    }
+
+    script! {
+        OP_2DUP
+        { m31_lincomb2(MOD - q, p) }
+        OP_TOALTSTACK
+        { m31_lincomb2(p, q) }
+        OP_FROMALTSTACK
+    }
+}
+
+pub fn cm31_toaltstack() -> Script {
+    script! {
+        OP_TOALTSTACK
+        OP_TOALTSTACK
+    }
+}
+
+pub fn cm31_fromaltstack() -> Script {
+    script! {
+        OP_FROMALTSTACK
+        OP_FROMALTSTACK
+    }
+}
+
+pub fn cm31_copy(offset: usize) -> Script {
+    let a = offset * 2 + 2 - 1;
+
+    script! {
+        for _ in 0..2 {
+            { a } OP_PICK
+        }
+    }
+}
+
+pub fn cm31_roll(offset: usize) -> Script {
+    let a = offset * 2 + 2 - 1;
+
+    script! {
+        for _ in 0..2 {
+            { a } OP_ROLL
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::exec::execute_script;
+    use crate::treepp::*;
+    use crate::{
+        cm31_add, cm31_conjugate, cm31_copy, cm31_double, cm31_equalverify, cm31_inverse,
+        cm31_is_square, cm31_mul, cm31_mul_by_constant, cm31_mul_m31, cm31_neg, cm31_norm,
+        cm31_roll, cm31_sqrt, cm31_sqrt_with_hint, cm31_square, cm31_sub, CM31,
+    };
+    use core::ops::{Add, Mul, Neg, Sub};
+    use p3_field::extension::Complex;
+    use p3_field::{AbstractField, Field, PrimeField32};
+    use p3_mersenne_31::Mersenne31 as P3M31;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+
+    type F = Complex<P3M31>;
+
+    #[test]
+    fn test_cm31_add_sub_double_neg() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!("cm31 add: {}", cm31_add().len());
+        eprintln!("cm31 sub: {}", cm31_sub().len());
+
+        for _ in 0..20 {
+            let a = rng.gen::<F>();
+            let b = rng.gen::<F>();
+
+            let sum = a.add(b);
+            let diff = a.sub(b);
+            let double = a.double();
+            let neg = a.neg();
+
+            let script = script! {
+                { a.imag().as_canonical_u32() } { a.real().as_canonical_u32() }
+                { b.imag().as_canonical_u32() } { b.real().as_canonical_u32() }
+                cm31_add
+                { sum.imag().as_canonical_u32() } { sum.real().as_canonical_u32() }
+                cm31_equalverify
+                { a.imag().as_canonical_u32() } { a.real().as_canonical_u32() }
+                { b.imag().as_canonical_u32() } { b.real().as_canonical_u32() }
+                cm31_sub
+                { diff.imag().as_canonical_u32() } { diff.real().as_canonical_u32() }
+                cm31_equalverify
+                { a.imag().as_canonical_u32() } { a.real().as_canonical_u32() }
+                cm31_double
+                { double.imag().as_canonical_u32() } { double.real().as_canonical_u32() }
+                cm31_equalverify
+                { a.imag().as_canonical_u32() } { a.real().as_canonical_u32() }
+                cm31_neg
+                { neg.imag().as_canonical_u32() } { neg.real().as_canonical_u32() }
+                cm31_equalverify
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_cm31_mul_square() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!("cm31 mul: {}", cm31_mul().len());
+        eprintln!("cm31 square: {}", cm31_square().len());
+
+        for _ in 0..20 {
+            let a = rng.gen::<F>();
+            let b = rng.gen::<F>();
+
+            let prod = a.mul(b);
+            let square = a.square();
+
+            let script = script! {
+                { a.imag().as_canonical_u32() } { a.real().as_canonical_u32() }
+                { b.imag().as_canonical_u32() } { b.real().as_canonical_u32() }
+                cm31_mul
+                { prod.imag().as_canonical_u32() } { prod.real().as_canonical_u32() }
+                cm31_equalverify
+                { a.imag().as_canonical_u32() } { a.real().as_canonical_u32() }
+                cm31_square
+                { square.imag().as_canonical_u32() } { square.real().as_canonical_u32() }
+                cm31_equalverify
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_cm31_conjugate_norm_inverse() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!("cm31 norm: {}", cm31_norm().len());
+        eprintln!("cm31 inverse: {}", cm31_inverse().len());
+
+        for _ in 0..10 {
+            let a = rng.gen::<F>();
+
+            let conjugate = F::new(a.real(), a.imag().neg());
+            let norm = a.real().square() + a.imag().square();
+            let inverse = a.inverse();
+
+            let script = script! {
+                { a.imag().as_canonical_u32() } { a.real().as_canonical_u32() }
+                cm31_conjugate
+                { conjugate.imag().as_canonical_u32() } { conjugate.real().as_canonical_u32() }
+                cm31_equalverify
+                { a.imag().as_canonical_u32() } { a.real().as_canonical_u32() }
+                cm31_norm
+                { norm.as_canonical_u32() }
+                OP_EQUALVERIFY
+                { a.imag().as_canonical_u32() } { a.real().as_canonical_u32() }
+                cm31_inverse
+                { inverse.imag().as_canonical_u32() } { inverse.real().as_canonical_u32() }
+                cm31_equalverify
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_cm31_mul_m31() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!("cm31 mul_by_m31: {}", cm31_mul_m31().len());
+
+        let a = rng.gen::<F>();
+        let b = rng.gen::<P3M31>();
+        let c = a.mul(F::new(b, P3M31::zero()));
+
+        let script = script! {
+            { a.imag().as_canonical_u32() } { a.real().as_canonical_u32() }
+            { b.as_canonical_u32() }
+            cm31_mul_m31
+            { c.imag().as_canonical_u32() } { c.real().as_canonical_u32() }
+            cm31_equalverify
+            OP_TRUE
+        };
+        let exec_result = execute_script(script);
+        assert!(exec_result.success);
+    }
+
+    #[test]
+    fn test_cm31_mul_by_constant() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+
+        let mut constants = vec![
+            F::zero(),
+            F::one(),
+            F::one().neg(),
+            F::new(P3M31::zero(), P3M31::one()),
+        ];
+        for _ in 0..10 {
+            constants.push(rng.gen::<F>());
+        }
+
+        for c in constants {
+            let mul_script =
+                cm31_mul_by_constant([c.imag().as_canonical_u32(), c.real().as_canonical_u32()]);
+            eprintln!("cm31 mul_by_constant: {}", mul_script.len());
+
+            let a = rng.gen::<F>();
+            let b = a.mul(c);
+
+            let script = script! {
+                { a.imag().as_canonical_u32() } { a.real().as_canonical_u32() }
+                { mul_script.clone() }
+                { b.imag().as_canonical_u32() } { b.real().as_canonical_u32() }
+                cm31_equalverify
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_cm31_sqrt() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!("cm31 is_square: {}", cm31_is_square().len());
+        eprintln!("cm31 sqrt: {}", cm31_sqrt().len());
+        eprintln!("cm31 sqrt with hint: {}", cm31_sqrt_with_hint().len());
+
+        let order = P3M31::ORDER_U32 as u64;
+
+        for _ in 0..5 {
+            let a = rng.gen::<F>();
+            let b = a.square();
+
+            // a + b * i is a square if and only if its norm is a square
+            let norm = a.real().square() + a.imag().square();
+            let is_square = norm.exp_u64((order - 1) / 2) == P3M31::one();
+
+            let script = script! {
+                { a.imag().as_canonical_u32() } { a.real().as_canonical_u32() }
+                cm31_is_square
+                { is_square as u32 }
+                OP_EQUALVERIFY
+                { b.imag().as_canonical_u32() } { b.real().as_canonical_u32() }
+                cm31_is_square
+                OP_VERIFY
+                { b.imag().as_canonical_u32() } { b.real().as_canonical_u32() }
+                cm31_sqrt
+                cm31_square
+                { b.imag().as_canonical_u32() } { b.real().as_canonical_u32() }
+                cm31_equalverify
+                { b.imag().as_canonical_u32() } { b.real().as_canonical_u32() }
+                { a.imag().as_canonical_u32() } { a.real().as_canonical_u32() }
+                cm31_sqrt_with_hint
+                { a.imag().as_canonical_u32() } { a.real().as_canonical_u32() }
+                cm31_equalverify
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+
+            let script = script! {
+                { a.imag().as_canonical_u32() } { a.real().as_canonical_u32() }
+                cm31_sqrt
+                OP_2DROP
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert_eq!(exec_result.success, is_square);
+        }
+    }
+
+    #[test]
+    fn test_cm31_native() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+
+        for _ in 0..20 {
+            let a = rng.gen::<F>();
+            let b = rng.gen::<F>();
+
+            let a_native = CM31::from(a);
+            let b_native = CM31::from(b);
+
+            assert_eq!(CM31::from(a.add(b)), a_native + b_native);
+            assert_eq!(CM31::from(a.sub(b)), a_native - b_native);
+            assert_eq!(CM31::from(a.mul(b)), a_native * b_native);
+            assert_eq!(CM31::from(a.inverse()), a_native.inverse());
+
+            let script = script! {
+                { a_native }
+                { b_native }
+                cm31_mul
+                { a_native * b_native }
+                cm31_equalverify
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_cm31_copy_roll() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+
+        let a = rng.gen::<F>();
+        let b = rng.gen::<F>();
+
+        let script = script! {
+            { a.imag().as_canonical_u32() } { a.real().as_canonical_u32() }
+            { b.imag().as_canonical_u32() } { b.real().as_canonical_u32() }
+            { cm31_copy(1) }
+            { a.imag().as_canonical_u32() } { a.real().as_canonical_u32() }
+            cm31_equalverify
+            { cm31_roll(1) }
+            { a.imag().as_canonical_u32() } { a.real().as_canonical_u32() }
+            cm31_equalverify
+            { b.imag().as_canonical_u32() } { b.real().as_canonical_u32() }
+            cm31_equalverify
+            OP_TRUE
+        };
+        let exec_result = execute_script(script);
+        assert!(exec_result.success);
+    }
+}
@@ -0,0 +1,140 @@
+use crate::gadgets::gadgets;
+use crate::stack_effect::{small_int, stack_usage, StackUsage};
+use crate::treepp::Script;
+use bitcoin::script::Instruction;
+
+/// Opcode counts of a script, grouped by the opcode classes of the reference interpreter.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OpcodeCounts {
+    /// Data pushes and small integers.
+    pub push: usize,
+    /// `OP_IF`, `OP_NOTIF`, `OP_ELSE`, `OP_ENDIF`, `OP_VERIFY` and the other flow control opcodes.
+    pub flow: usize,
+    /// Stack manipulation, including `OP_TOALTSTACK` and `OP_FROMALTSTACK`.
+    pub stack: usize,
+    /// `OP_EQUAL`, `OP_EQUALVERIFY` and the bitwise opcodes.
+    pub bitwise: usize,
+    /// Numeric opcodes, including `OP_MUL`, `OP_DIV` and the numeric comparisons.
+    pub arithmetic: usize,
+    /// Splice, crypto, locktime and reserved opcodes.
+    pub other: usize,
+}
+
+impl OpcodeCounts {
+    pub fn total(&self) -> usize {
+        self.push + self.flow + self.stack + self.bitwise + self.arithmetic + self.other
+    }
+}
+
+/// The cost of a script in a tapscript leaf.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cost {
+    pub bytes: usize,
+    /// The script is witness data, so each byte weighs one unit.
+    pub weight_units: usize,
+    pub opcodes: OpcodeCounts,
+    /// `None` if the stack usage cannot be derived statically, see `stack_usage`.
+    pub stack: Option<StackUsage>,
+}
+
+pub fn script_cost(script: &Script) -> Cost {
+    let mut opcodes = OpcodeCounts::default();
+    for instruction in script.instructions() {
+        match instruction {
+            Ok(Instruction::PushBytes(_)) => opcodes.push += 1,
+            Ok(Instruction::Op(op)) if small_int(op).is_some() => opcodes.push += 1,
+            Ok(Instruction::Op(op)) => match op.to_u8() {
+                0x61..=0x6a => opcodes.flow += 1,
+                0x6b..=0x7d => opcodes.stack += 1,
+                0x83..=0x8a => opcodes.bitwise += 1,
+                0x8b..=0xa5 => opcodes.arithmetic += 1,
+                _ => opcodes.other += 1,
+            },
+            Err(_) => opcodes.other += 1,
+        }
+    }
+
+    Cost {
+        bytes: script.len(),
+        weight_units: script.len(),
+        opcodes,
+        stack: stack_usage(script),
+    }
+}
+
+/// The costs of all gadgets in the crate, with representative parameters.
+pub fn gadget_costs() -> Vec<(String, Cost)> {
+    gadgets()
+        .into_iter()
+        .map(|gadget| {
+            let cost = script_cost(&gadget.script);
+            (gadget.name, cost)
+        })
+        .collect()
+}
+
+/// Renders `gadget_costs` as a markdown table, which is the one in the README.
+pub fn cost_table() -> String {
+    let mut table = String::from(
+        "| gadget | weight units | pushes | flow | stack | bitwise | arithmetic | inputs | outputs | max stack | max altstack |\n\
+         |---|---:|---:|---:|---:|---:|---:|---:|---:|---:|---:|\n",
+    );
+
+    for (name, cost) in gadget_costs() {
+        let ops = cost.opcodes;
+        let stack = match cost.stack {
+            Some(usage) => format!(
+                "{} | {} | {} | {}",
+                usage.inputs, usage.outputs, usage.max_main, usage.max_alt
+            ),
+            None => "- | - | - | -".to_string(),
+        };
+        table += &format!(
+            "| `{}` | {} | {} | {} | {} | {} | {} | {} |\n",
+            name,
+            cost.weight_units,
+            ops.push,
+            ops.flow,
+            ops.stack,
+            ops.bitwise,
+            ops.arithmetic,
+            stack
+        );
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{cost_table, m31_add, m31_mul, qm31_mul, script_cost, StackUsage};
+
+    #[test]
+    fn test_script_cost() {
+        let cost = script_cost(&m31_add());
+        assert_eq!(cost.bytes, 18);
+        assert_eq!(cost.weight_units, 18);
+        assert_eq!(cost.opcodes.total(), 10);
+        assert_eq!(
+            cost.stack,
+            Some(StackUsage {
+                inputs: 2,
+                outputs: 1,
+                alt_inputs: 0,
+                alt_outputs: 0,
+                max_main: 3,
+                max_alt: 0,
+            })
+        );
+
+        assert_eq!(script_cost(&m31_mul()).weight_units, 215);
+        assert_eq!(script_cost(&qm31_mul()).weight_units, 2521);
+    }
+
+    #[test]
+    fn test_cost_table() {
+        // regenerate the README table with `println!("{}", cost_table())` when a gadget changes
+        let readme = include_str!("../README.md");
+        assert!(readme.contains(&cost_table()));
+    }
+}
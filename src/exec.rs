@@ -0,0 +1,124 @@
+use crate::treepp::Script;
+use crate::witness::{decode_witness, Witness};
+use bitcoin::hashes::Hash;
+use bitcoin::{TapLeafHash, Transaction};
+use bitcoin_scriptexec::{
+    Exec, ExecCtx, ExecError, ExecStats, ExecuteInfo, FmtStack, Options, TxTemplate,
+};
+
+/// The outcome of running a script with `execute_script_with_witness`.
+#[derive(Clone, Debug)]
+pub struct ExecResult {
+    pub success: bool,
+    pub error: Option<ExecError>,
+    /// The final main stack, from the bottom to the top.
+    pub final_stack: Vec<Vec<u8>>,
+    pub stats: ExecStats,
+    /// The maximum number of stack items during the execution, as reported by the interpreter.
+    pub max_stack_depth: usize,
+}
+
+impl ExecResult {
+    /// Decodes the whole final stack as a sequence of field elements, see `decode_witness`.
+    pub fn final_stack_as<T: Witness>(&self) -> Option<Vec<T>> {
+        decode_witness(&self.final_stack)
+    }
+}
+
+impl From<ExecuteInfo> for ExecResult {
+    fn from(info: ExecuteInfo) -> ExecResult {
+        ExecResult {
+            success: info.success,
+            error: info.error.clone(),
+            final_stack: final_stack_items(&info),
+            max_stack_depth: info.stats.max_nb_stack_items,
+            stats: info.stats,
+        }
+    }
+}
+
+/// The final stack of an execution, from the bottom to the top.
+pub fn final_stack_items(info: &ExecuteInfo) -> Vec<Vec<u8>> {
+    (0..info.final_stack.len())
+        .map(|i| info.final_stack.get(i))
+        .collect()
+}
+
+/// Runs `script` as a tapscript with `OP_MUL` and `OP_DIV` enabled.
+pub fn execute_script(script: Script) -> ExecResult {
+    execute_script_with_witness(script, vec![])
+}
+
+/// Runs `script` as a tapscript with `OP_MUL` and `OP_DIV` enabled, on top of the witness
+/// items, which are listed from the bottom of the stack to the top.
+pub fn execute_script_with_witness(script: Script, witness: Vec<Vec<u8>>) -> ExecResult {
+    execute_script_info(script, witness).into()
+}
+
+fn execute_script_info(script: Script, witness: Vec<Vec<u8>>) -> ExecuteInfo {
+    let mut exec = Exec::new(
+        ExecCtx::Tapscript,
+        Options::default_with_mul_div(),
+        TxTemplate {
+            tx: Transaction {
+                version: bitcoin::transaction::Version::TWO,
+                lock_time: bitcoin::locktime::absolute::LockTime::ZERO,
+                input: vec![],
+                output: vec![],
+            },
+            prevouts: vec![],
+            input_idx: 0,
+            taproot_annex_scriptleaf: Some((TapLeafHash::all_zeros(), None)),
+        },
+        script,
+        witness,
+    )
+    .expect("error creating exec");
+
+    loop {
+        if exec.exec_next().is_err() {
+            break;
+        }
+    }
+    let res = exec.result().unwrap();
+    ExecuteInfo {
+        success: res.success,
+        error: res.error.clone(),
+        last_opcode: res.opcode,
+        final_stack: FmtStack(exec.stack().clone()),
+        remaining_script: exec.remaining_script().to_asm_string(),
+        stats: exec.stats().clone(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::exec::{execute_script, execute_script_with_witness};
+    use crate::treepp::*;
+    use crate::{encode_witness, qm31_mul, QM31};
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn test_exec() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+
+        let a: QM31 = prng.gen();
+        let b: QM31 = prng.gen();
+
+        let result = execute_script_with_witness(qm31_mul(), encode_witness(&[a, b]));
+        assert_eq!(result.final_stack_as::<QM31>(), Some(vec![a * b]));
+        assert!(result.max_stack_depth >= 8);
+
+        let result = execute_script(script! {
+            { a }
+            { b }
+            qm31_mul
+            { a * b }
+            qm31_equalverify
+            OP_TRUE
+        });
+        assert!(result.success);
+        assert!(result.error.is_none());
+    }
+}
@@ -0,0 +1,389 @@
+use crate::cm31::*;
+use crate::m31::*;
+use crate::prime_field31::*;
+use crate::qm31::*;
+use crate::treepp::*;
+use crate::witness::Witness;
+use core::fmt::Debug;
+use core::ops::{Add, Mul, Neg, Sub};
+
+/// The gadgets that every field provides, so that higher-level scripts can be written once
+/// and instantiated for M31, CM31, QM31, and `PrimeField31`.
+///
+/// An element takes `LIMBS` stack elements, laid out as the `Witness` encoding of `Native`,
+/// and all gadgets expect and return canonical elements.
+pub trait ScriptField {
+    /// The native type, used to compute expected outputs and hints.
+    type Native: Copy
+        + Debug
+        + PartialEq
+        + Witness
+        + Add<Output = Self::Native>
+        + Sub<Output = Self::Native>
+        + Mul<Output = Self::Native>
+        + Neg<Output = Self::Native>;
+
+    /// The number of stack elements of an element.
+    const LIMBS: usize;
+
+    /// Pushes `value`, in the same layout as its witness.
+    fn push(value: Self::Native) -> Script;
+
+    fn add() -> Script;
+    fn sub() -> Script;
+    fn mul() -> Script;
+    fn neg() -> Script;
+
+    fn double() -> Script {
+        script! {
+            { Self::copy(0) }
+            { Self::add() }
+        }
+    }
+
+    fn square() -> Script {
+        script! {
+            { Self::copy(0) }
+            { Self::mul() }
+        }
+    }
+
+    fn equalverify() -> Script {
+        script! {
+            for i in 0..Self::LIMBS - 1 {
+                { Self::LIMBS - i } OP_ROLL
+                OP_EQUALVERIFY
+            }
+            OP_EQUALVERIFY
+        }
+    }
+
+    /// Copies the element `offset` elements below the top, where 0 is the top element.
+    fn copy(offset: usize) -> Script {
+        let a = offset * Self::LIMBS + Self::LIMBS - 1;
+
+        script! {
+            for _ in 0..Self::LIMBS {
+                { a } OP_PICK
+            }
+        }
+    }
+
+    /// Moves the element `offset` elements below the top to the top.
+    fn roll(offset: usize) -> Script {
+        let a = offset * Self::LIMBS + Self::LIMBS - 1;
+
+        script! {
+            for _ in 0..Self::LIMBS {
+                { a } OP_ROLL
+            }
+        }
+    }
+
+    fn drop() -> Script {
+        script! {
+            for _ in 0..Self::LIMBS / 2 {
+                OP_2DROP
+            }
+            if Self::LIMBS % 2 == 1 {
+                OP_DROP
+            }
+        }
+    }
+
+    fn toaltstack() -> Script {
+        script! {
+            for _ in 0..Self::LIMBS {
+                OP_TOALTSTACK
+            }
+        }
+    }
+
+    fn fromaltstack() -> Script {
+        script! {
+            for _ in 0..Self::LIMBS {
+                OP_FROMALTSTACK
+            }
+        }
+    }
+}
+
+impl ScriptField for M31 {
+    type Native = M31;
+    const LIMBS: usize = 1;
+
+    fn push(value: M31) -> Script {
+        script! { { value } }
+    }
+
+    fn add() -> Script {
+        m31_add()
+    }
+
+    fn sub() -> Script {
+        m31_sub()
+    }
+
+    fn mul() -> Script {
+        m31_mul()
+    }
+
+    fn neg() -> Script {
+        // `m31_neg` maps zero to p, so subtract from zero instead to stay canonical
+        script! {
+            0 OP_SWAP m31_sub
+        }
+    }
+
+    fn double() -> Script {
+        m31_double()
+    }
+
+    fn square() -> Script {
+        m31_square()
+    }
+}
+
+impl ScriptField for CM31 {
+    type Native = CM31;
+    const LIMBS: usize = 2;
+
+    fn push(value: CM31) -> Script {
+        script! { { value } }
+    }
+
+    fn add() -> Script {
+        cm31_add()
+    }
+
+    fn sub() -> Script {
+        cm31_sub()
+    }
+
+    fn mul() -> Script {
+        cm31_mul()
+    }
+
+    fn neg() -> Script {
+        cm31_neg()
+    }
+
+    fn double() -> Script {
+        cm31_double()
+    }
+
+    fn square() -> Script {
+        cm31_square()
+    }
+
+    fn equalverify() -> Script {
+        cm31_equalverify()
+    }
+}
+
+impl ScriptField for QM31 {
+    type Native = QM31;
+    const LIMBS: usize = 4;
+
+    fn push(value: QM31) -> Script {
+        script! { { value } }
+    }
+
+    fn add() -> Script {
+        qm31_add()
+    }
+
+    fn sub() -> Script {
+        qm31_sub()
+    }
+
+    fn mul() -> Script {
+        qm31_mul()
+    }
+
+    fn neg() -> Script {
+        qm31_neg()
+    }
+
+    fn double() -> Script {
+        qm31_double()
+    }
+
+    fn square() -> Script {
+        qm31_square()
+    }
+}
+
+impl<const P: u32> ScriptField for PrimeField31<P> {
+    type Native = Self;
+    const LIMBS: usize = 1;
+
+    fn push(value: Self) -> Script {
+        script! { { value } }
+    }
+
+    fn add() -> Script {
+        pf31_add::<P>()
+    }
+
+    fn sub() -> Script {
+        pf31_sub::<P>()
+    }
+
+    fn mul() -> Script {
+        pf31_mul::<P>()
+    }
+
+    fn neg() -> Script {
+        pf31_neg::<P>()
+    }
+
+    fn double() -> Script {
+        pf31_double::<P>()
+    }
+
+    fn square() -> Script {
+        pf31_square::<P>()
+    }
+}
+
+/// Evaluates a polynomial of the given degree with Horner's rule.
+pub fn horner_eval<F: ScriptField>(degree: usize) -> Script {
+    // input stack: c_0 c_1 ... c_d z
+    // where d is the degree and c_0, ..., c_d are the coefficients, with the leading one
+    // right below the point z
+    //
+    // output stack: c_0 + c_1 * z + ... + c_d * z^d
+    if degree == 0 {
+        return F::drop();
+    }
+
+    script! {
+        // current stack after each step: c_0 ... c_{k - 1} z acc
+        { F::roll(1) }
+        for _ in 1..degree {
+            { F::copy(1) }
+            { F::mul() }
+            { F::roll(2) }
+            { F::add() }
+        }
+
+        // the last step consumes z
+        { F::roll(1) }
+        { F::mul() }
+        { F::add() }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::exec::execute_script;
+    use crate::treepp::*;
+    use crate::{horner_eval, PrimeField31, ScriptField, CM31, M31, QM31};
+    use rand::distributions::{Distribution, Standard};
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+
+    fn test_field<F: ScriptField>()
+    where
+        Standard: Distribution<F::Native>,
+    {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+
+        for _ in 0..20 {
+            let a: F::Native = prng.gen();
+            let b: F::Native = prng.gen();
+
+            let script = script! {
+                { F::push(a) }
+                { F::push(b) }
+                { F::copy(1) }
+                { F::copy(1) }
+                { F::add() }
+                { F::push(a + b) }
+                { F::equalverify() }
+                { F::copy(1) }
+                { F::copy(1) }
+                { F::sub() }
+                { F::push(a - b) }
+                { F::equalverify() }
+                { F::copy(1) }
+                { F::copy(1) }
+                { F::mul() }
+                { F::push(a * b) }
+                { F::equalverify() }
+                { F::roll(1) }
+                { F::toaltstack() }
+                { F::push(b) }
+                { F::equalverify() }
+                { F::fromaltstack() }
+                { F::copy(0) }
+                { F::neg() }
+                { F::push(-a) }
+                { F::equalverify() }
+                { F::copy(0) }
+                { F::double() }
+                { F::push(a + a) }
+                { F::equalverify() }
+                { F::square() }
+                { F::push(a * a) }
+                { F::equalverify() }
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+
+        // zero stays canonical under negation
+        let a: F::Native = prng.gen();
+        let zero = a - a;
+        let script = script! {
+            { F::push(zero) }
+            { F::neg() }
+            { F::push(zero) }
+            { F::equalverify() }
+            OP_TRUE
+        };
+        let exec_result = execute_script(script);
+        assert!(exec_result.success);
+
+        for degree in 0..4 {
+            let coeffs: Vec<F::Native> = (0..=degree).map(|_| prng.gen()).collect();
+            let z: F::Native = prng.gen();
+            let value = coeffs.iter().rev().fold(zero, |acc, c| acc * z + *c);
+
+            let script = script! {
+                for c in coeffs.iter() {
+                    { F::push(*c) }
+                }
+                { F::push(z) }
+                { horner_eval::<F>(degree) }
+                { F::push(value) }
+                { F::equalverify() }
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_m31_field() {
+        test_field::<M31>();
+    }
+
+    #[test]
+    fn test_cm31_field() {
+        test_field::<CM31>();
+    }
+
+    #[test]
+    fn test_qm31_field() {
+        test_field::<QM31>();
+    }
+
+    #[test]
+    fn test_prime_field31_field() {
+        test_field::<PrimeField31<{ (1 << 30) - 35 }>>();
+    }
+}
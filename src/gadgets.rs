@@ -0,0 +1,291 @@
+use crate::babybear::*;
+use crate::babybear4::*;
+use crate::cm31::*;
+use crate::field::horner_eval;
+use crate::karatsuba_complex::{karatsuba_big, karatsuba_small};
+use crate::koalabear::*;
+use crate::koalabear4::*;
+use crate::m31::*;
+use crate::m31_acc::*;
+use crate::prime_field31::*;
+use crate::qm31::*;
+use crate::stack_effect::StackEffect;
+use crate::treepp::Script;
+
+/// A gadget instantiated with fixed parameters, together with its declared stack effect.
+#[derive(Clone, Debug)]
+pub struct Gadget {
+    pub name: String,
+    pub script: Script,
+    pub effect: StackEffect,
+}
+
+impl Gadget {
+    /// A gadget that leaves the altstack as it found it.
+    pub fn new(name: impl Into<String>, script: Script, inputs: usize, outputs: usize) -> Self {
+        Self::with_effect(name, script, StackEffect::new(inputs, outputs))
+    }
+
+    pub fn with_effect(name: impl Into<String>, script: Script, effect: StackEffect) -> Self {
+        Gadget {
+            name: name.into(),
+            script,
+            effect,
+        }
+    }
+}
+
+// the largest prime below 2^30, as an example of a field without dedicated gadgets
+const PF31_P: u32 = (1 << 30) - 35;
+
+/// All gadgets in the crate, with representative parameters.
+pub fn gadgets() -> Vec<Gadget> {
+    let m31_c = 1_234_567_890;
+    let cm31_c = [1_234_567_890, 987_654_321];
+    let qm31_c = [1_234_567_890, 987_654_321, 1_357_924_680, 246_813_579];
+
+    vec![
+        Gadget::new("m31_add", m31_add(), 2, 1),
+        Gadget::new("m31_sub", m31_sub(), 2, 1),
+        Gadget::new("m31_neg", m31_neg(), 1, 1),
+        Gadget::new("m31_double", m31_double(), 1, 1),
+        Gadget::new("m31_mul", m31_mul(), 2, 1),
+        Gadget::new("m31_square", m31_square(), 1, 1),
+        Gadget::new(
+            format!("m31_mul_by_constant({})", m31_c),
+            m31_mul_by_constant(m31_c),
+            1,
+            1,
+        ),
+        Gadget::new(
+            "m31_mul_by_constant(2^10)",
+            m31_mul_by_constant(1 << 10),
+            1,
+            1,
+        ),
+        Gadget::new("m31_inverse", m31_inverse(), 1, 1),
+        Gadget::new("m31_inverse_with_hint", m31_inverse_with_hint(), 2, 1),
+        Gadget::new("m31_batch_inverse(4)", m31_batch_inverse(4), 4, 4),
+        Gadget::new(
+            "m31_batch_inverse_with_hint(4)",
+            m31_batch_inverse_with_hint(4),
+            9,
+            4,
+        ),
+        Gadget::new("m31_pow_const(p - 2)", m31_pow_const(MOD - 2), 1, 1),
+        Gadget::new("m31_pow_var", m31_pow_var(), 2, 1),
+        Gadget::new("m31_is_square", m31_is_square(), 1, 1),
+        Gadget::new("m31_sqrt", m31_sqrt(), 1, 1),
+        Gadget::new("m31_sqrt_with_hint", m31_sqrt_with_hint(), 2, 1),
+        Gadget::new("m31_to_bits", m31_to_bits(), 1, 31),
+        Gadget::new("m31_to_n31", m31_to_n31(), 1, 1),
+        Gadget::new("n31_to_m31", n31_to_m31(), 1, 1),
+        Gadget::new("m31_add_n31", m31_add_n31(), 2, 1),
+        Gadget::new("n31_add_m31", n31_add_m31(), 2, 1),
+        Gadget::new("n31_add", n31_add(), 2, 1),
+        Gadget::new("n31_sub", n31_sub(), 2, 1),
+        Gadget::new("n31_neg", n31_neg(), 1, 1),
+        Gadget::new("n31_double", n31_double(), 1, 1),
+        Gadget::new("m31_to_acc", m31_to_acc(), 1, 3),
+        Gadget::new("m31_mul_unreduced", m31_mul_unreduced(), 2, 3),
+        Gadget::new("m31_acc_normalize", m31_acc_normalize(), 3, 3),
+        Gadget::new(
+            "m31_acc_add(MUL, MUL)",
+            m31_acc_add(M31Acc::MUL, M31Acc::MUL).0,
+            6,
+            3,
+        ),
+        Gadget::new("m31_acc_reduce(MUL)", m31_acc_reduce(M31Acc::MUL), 3, 1),
+        Gadget::new("m31_inner_product(4)", m31_inner_product(4), 8, 1),
+        Gadget::new("karatsuba_small", karatsuba_small(), 4, 2),
+        Gadget::new("karatsuba_big", karatsuba_big(), 8, 6),
+        Gadget::new("cm31_add", cm31_add(), 4, 2),
+        Gadget::new("cm31_sub", cm31_sub(), 4, 2),
+        Gadget::new("cm31_neg", cm31_neg(), 2, 2),
+        Gadget::new("cm31_double", cm31_double(), 2, 2),
+        Gadget::new("cm31_mul", cm31_mul(), 4, 2),
+        Gadget::new("cm31_square", cm31_square(), 2, 2),
+        Gadget::new("cm31_mul_m31", cm31_mul_m31(), 3, 2),
+        Gadget::new(
+            format!("cm31_mul_by_constant({:?})", cm31_c),
+            cm31_mul_by_constant(cm31_c),
+            2,
+            2,
+        ),
+        Gadget::new(
+            "cm31_mul_by_constant(2 + i)",
+            cm31_mul_by_constant([1, 2]),
+            2,
+            2,
+        ),
+        Gadget::new("cm31_conjugate", cm31_conjugate(), 2, 2),
+        Gadget::new("cm31_norm", cm31_norm(), 2, 1),
+        Gadget::new("cm31_inverse", cm31_inverse(), 2, 2),
+        Gadget::new("cm31_is_square", cm31_is_square(), 2, 1),
+        Gadget::new("cm31_sqrt", cm31_sqrt(), 2, 2),
+        Gadget::new("cm31_sqrt_with_hint", cm31_sqrt_with_hint(), 4, 2),
+        Gadget::new("cm31_equalverify", cm31_equalverify(), 4, 0),
+        Gadget::with_effect(
+            "cm31_toaltstack",
+            cm31_toaltstack(),
+            StackEffect::new(2, 0).with_alt(0, 2),
+        ),
+        Gadget::with_effect(
+            "cm31_fromaltstack",
+            cm31_fromaltstack(),
+            StackEffect::new(0, 2).with_alt(2, 0),
+        ),
+        Gadget::new("cm31_copy(1)", cm31_copy(1), 4, 6),
+        Gadget::new("cm31_roll(1)", cm31_roll(1), 4, 4),
+        Gadget::new("qm31_add", qm31_add(), 8, 4),
+        Gadget::new("qm31_sub", qm31_sub(), 8, 4),
+        Gadget::new("qm31_neg", qm31_neg(), 4, 4),
+        Gadget::new("qm31_double", qm31_double(), 4, 4),
+        Gadget::new("qm31_mul", qm31_mul(), 8, 4),
+        Gadget::new("qm31_square", qm31_square(), 4, 4),
+        Gadget::new("qm31_mul_m31", qm31_mul_m31(), 5, 4),
+        Gadget::new("qm31_mul_cm31", qm31_mul_cm31(), 6, 4),
+        Gadget::new(
+            format!("qm31_mul_by_constant({:?})", qm31_c),
+            qm31_mul_by_constant(qm31_c),
+            4,
+            4,
+        ),
+        Gadget::new(
+            "qm31_mul_by_constant(2 + i)",
+            qm31_mul_by_constant([0, 0, 1, 2]),
+            4,
+            4,
+        ),
+        Gadget::new("qm31_conjugate", qm31_conjugate(), 4, 4),
+        Gadget::new("qm31_frobenius(1)", qm31_frobenius(1), 4, 4),
+        Gadget::new("qm31_norm_to_cm31", qm31_norm_to_cm31(), 4, 2),
+        Gadget::new("qm31_norm_to_m31", qm31_norm_to_m31(), 4, 1),
+        Gadget::new("qm31_inverse", qm31_inverse(), 4, 4),
+        Gadget::new("qm31_inverse_with_hint", qm31_inverse_with_hint(), 8, 4),
+        Gadget::new("qm31_batch_inverse(4)", qm31_batch_inverse(4), 16, 16),
+        Gadget::new(
+            "qm31_batch_inverse_with_hint(4)",
+            qm31_batch_inverse_with_hint(4),
+            36,
+            16,
+        ),
+        Gadget::new("qm31_div", qm31_div(), 8, 4),
+        Gadget::new("qm31_div_with_hint", qm31_div_with_hint(), 12, 4),
+        Gadget::new("qm31_is_square", qm31_is_square(), 4, 1),
+        Gadget::new("qm31_sqrt", qm31_sqrt(), 4, 4),
+        Gadget::new("qm31_sqrt_with_hint", qm31_sqrt_with_hint(), 8, 4),
+        Gadget::new("qm31_horner_eval(4)", qm31_horner_eval(4), 24, 4),
+        Gadget::new("horner_eval::<M31>(4)", horner_eval::<M31>(4), 6, 1),
+        Gadget::new("horner_eval::<CM31>(4)", horner_eval::<CM31>(4), 12, 2),
+        Gadget::new(
+            "qm31_horner_eval_const(degree 4)",
+            qm31_horner_eval_const(&[QM31::from_limbs(qm31_c); 5]),
+            4,
+            4,
+        ),
+        Gadget::new("qm31_inner_product(4)", qm31_inner_product(4), 32, 4),
+        Gadget::new("qm31_equalverify", qm31_equalverify(), 8, 0),
+        Gadget::with_effect(
+            "qm31_toaltstack",
+            qm31_toaltstack(),
+            StackEffect::new(4, 0).with_alt(0, 4),
+        ),
+        Gadget::with_effect(
+            "qm31_fromaltstack",
+            qm31_fromaltstack(),
+            StackEffect::new(0, 4).with_alt(4, 0),
+        ),
+        Gadget::new("qm31_copy(1)", qm31_copy(1), 8, 12),
+        Gadget::new("qm31_roll(1)", qm31_roll(1), 8, 8),
+        Gadget::new("babybear_add", babybear_add(), 2, 1),
+        Gadget::new("babybear_sub", babybear_sub(), 2, 1),
+        Gadget::new("babybear_neg", babybear_neg(), 1, 1),
+        Gadget::new("babybear_double", babybear_double(), 1, 1),
+        Gadget::new("babybear_mul", babybear_mul(), 2, 1),
+        Gadget::new(
+            "babybear_mul_by_small_constant(11)",
+            babybear_mul_by_small_constant(11),
+            1,
+            1,
+        ),
+        Gadget::new("babybear_inverse", babybear_inverse(), 1, 1),
+        Gadget::new("babybear4_add", babybear4_add(), 8, 4),
+        Gadget::new("babybear4_sub", babybear4_sub(), 8, 4),
+        Gadget::new("babybear4_mul", babybear4_mul(), 8, 4),
+        Gadget::new("babybear4_square", babybear4_square(), 4, 4),
+        Gadget::new("babybear4_mul_babybear", babybear4_mul_babybear(), 5, 4),
+        Gadget::new("babybear4_inverse", babybear4_inverse(), 4, 4),
+        Gadget::new("babybear4_equalverify", babybear4_equalverify(), 8, 0),
+        Gadget::with_effect(
+            "babybear4_toaltstack",
+            babybear4_toaltstack(),
+            StackEffect::new(4, 0).with_alt(0, 4),
+        ),
+        Gadget::with_effect(
+            "babybear4_fromaltstack",
+            babybear4_fromaltstack(),
+            StackEffect::new(0, 4).with_alt(4, 0),
+        ),
+        Gadget::new("babybear4_copy(1)", babybear4_copy(1), 8, 12),
+        Gadget::new("babybear4_roll(1)", babybear4_roll(1), 8, 8),
+        Gadget::new("koalabear_add", koalabear_add(), 2, 1),
+        Gadget::new("koalabear_sub", koalabear_sub(), 2, 1),
+        Gadget::new("koalabear_neg", koalabear_neg(), 1, 1),
+        Gadget::new("koalabear_double", koalabear_double(), 1, 1),
+        Gadget::new("koalabear_mul", koalabear_mul(), 2, 1),
+        Gadget::new(
+            "koalabear_mul_by_small_constant(3)",
+            koalabear_mul_by_small_constant(3),
+            1,
+            1,
+        ),
+        Gadget::new("koalabear_inverse", koalabear_inverse(), 1, 1),
+        Gadget::new("koalabear4_add", koalabear4_add(), 8, 4),
+        Gadget::new("koalabear4_sub", koalabear4_sub(), 8, 4),
+        Gadget::new("koalabear4_mul", koalabear4_mul(), 8, 4),
+        Gadget::new("koalabear4_square", koalabear4_square(), 4, 4),
+        Gadget::new("koalabear4_mul_koalabear", koalabear4_mul_koalabear(), 5, 4),
+        Gadget::new("koalabear4_inverse", koalabear4_inverse(), 4, 4),
+        Gadget::new("koalabear4_equalverify", koalabear4_equalverify(), 8, 0),
+        Gadget::with_effect(
+            "koalabear4_toaltstack",
+            koalabear4_toaltstack(),
+            StackEffect::new(4, 0).with_alt(0, 4),
+        ),
+        Gadget::with_effect(
+            "koalabear4_fromaltstack",
+            koalabear4_fromaltstack(),
+            StackEffect::new(0, 4).with_alt(4, 0),
+        ),
+        Gadget::new("koalabear4_copy(1)", koalabear4_copy(1), 8, 12),
+        Gadget::new("koalabear4_roll(1)", koalabear4_roll(1), 8, 8),
+        Gadget::new("pf31_add::<2^30 - 35>", pf31_add::<PF31_P>(), 2, 1),
+        Gadget::new("pf31_sub::<2^30 - 35>", pf31_sub::<PF31_P>(), 2, 1),
+        Gadget::new("pf31_neg::<2^30 - 35>", pf31_neg::<PF31_P>(), 1, 1),
+        Gadget::new("pf31_double::<2^30 - 35>", pf31_double::<PF31_P>(), 1, 1),
+        Gadget::new("pf31_mul::<2^30 - 35>", pf31_mul::<PF31_P>(), 2, 1),
+        Gadget::new("pf31_square::<2^30 - 35>", pf31_square::<PF31_P>(), 1, 1),
+        Gadget::new(
+            "pf31_pow_const::<2^30 - 35>(p - 2)",
+            pf31_pow_const::<PF31_P>(PF31_P - 2),
+            1,
+            1,
+        ),
+        Gadget::new("pf31_inverse::<2^30 - 35>", pf31_inverse::<PF31_P>(), 1, 1),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{check_stack_effect, gadgets};
+
+    #[test]
+    fn test_gadget_stack_effects() {
+        for gadget in gadgets() {
+            if let Err(err) = check_stack_effect(&gadget.script, gadget.effect) {
+                panic!("{}: {:?}", gadget.name, err);
+            }
+        }
+    }
+}
@@ -90,6 +90,7 @@ pub fn karatsuba_big() -> Script {
 #[cfg(test)]
 mod test {
     use super::{karatsuba_big, karatsuba_small};
+    use crate::exec::execute_script;
     use crate::treepp::*;
     use core::ops::{Add, Mul, Sub};
     use p3_field::extension::Complex;
@@ -0,0 +1,60 @@
+use crate::treepp::*;
+use crate::two_adic31;
+
+pub(crate) const KOALABEAR_MOD: u32 = 127 * (1 << 24) + 1;
+
+pub fn koalabear_add() -> Script {
+    two_adic31::add(KOALABEAR_MOD)
+}
+
+pub fn koalabear_double() -> Script {
+    two_adic31::double(KOALABEAR_MOD)
+}
+
+pub fn koalabear_sub() -> Script {
+    two_adic31::sub(KOALABEAR_MOD)
+}
+
+pub fn koalabear_neg() -> Script {
+    two_adic31::neg(KOALABEAR_MOD)
+}
+
+pub fn koalabear_mul() -> Script {
+    two_adic31::mul(KOALABEAR_MOD)
+}
+
+pub fn koalabear_mul_by_small_constant(c: u32) -> Script {
+    two_adic31::mul_by_small_constant(KOALABEAR_MOD, c)
+}
+
+pub fn koalabear_inverse() -> Script {
+    // compute a^(p - 2) where p - 2 = 0b1111110 followed by 24 ones, with a window of three bits
+    //
+    // a^(2^3 - 1) = (a^2 * a)^2 * a
+    // a^(2^6 - 1) = (a^(2^3 - 1))^(2^3) * a^(2^3 - 1)
+    // a^(0b1111110) = (a^(2^6 - 1))^2
+    // then, 8 times, shift in three ones: t = t^(2^3) * a^(2^3 - 1)
+    //
+    // note: zero is mapped to zero
+    script! {
+        OP_DUP OP_DUP koalabear_mul
+        OP_OVER koalabear_mul
+        OP_DUP koalabear_mul
+        OP_SWAP koalabear_mul
+        // current stack: a^7
+
+        OP_DUP
+        for _ in 0..3 {
+            OP_DUP koalabear_mul
+        }
+        OP_OVER koalabear_mul
+        OP_DUP koalabear_mul
+        for _ in 0..8 {
+            for _ in 0..3 {
+                OP_DUP koalabear_mul
+            }
+            OP_OVER koalabear_mul
+        }
+        OP_NIP
+    }
+}
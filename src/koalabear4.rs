@@ -0,0 +1,56 @@
+use crate::koalabear::{koalabear_inverse, KOALABEAR_MOD};
+use crate::treepp::*;
+use crate::two_adic31;
+
+// An element a0 + a1 * x + a2 * x^2 + a3 * x^3 of the extension KoalaBear[x] / (x^4 - 3)
+// is kept on the stack as
+//
+//      a3 a2 a1 a0
+//
+// with a0 on top, in the same way as qm31.
+
+pub(crate) const KOALABEAR4_NONRESIDUE: u32 = 3;
+
+pub fn koalabear4_add() -> Script {
+    two_adic31::ext4_add(KOALABEAR_MOD)
+}
+
+pub fn koalabear4_sub() -> Script {
+    two_adic31::ext4_sub(KOALABEAR_MOD)
+}
+
+pub fn koalabear4_equalverify() -> Script {
+    two_adic31::ext4_equalverify()
+}
+
+pub fn koalabear4_mul() -> Script {
+    two_adic31::ext4_mul(KOALABEAR_MOD, KOALABEAR4_NONRESIDUE)
+}
+
+pub fn koalabear4_square() -> Script {
+    two_adic31::ext4_square(KOALABEAR_MOD, KOALABEAR4_NONRESIDUE)
+}
+
+pub fn koalabear4_mul_koalabear() -> Script {
+    two_adic31::ext4_mul_base(KOALABEAR_MOD)
+}
+
+pub fn koalabear4_inverse() -> Script {
+    two_adic31::ext4_inverse(KOALABEAR_MOD, KOALABEAR4_NONRESIDUE, koalabear_inverse())
+}
+
+pub fn koalabear4_toaltstack() -> Script {
+    two_adic31::ext4_toaltstack()
+}
+
+pub fn koalabear4_fromaltstack() -> Script {
+    two_adic31::ext4_fromaltstack()
+}
+
+pub fn koalabear4_copy(offset: usize) -> Script {
+    two_adic31::ext4_copy(offset)
+}
+
+pub fn koalabear4_roll(offset: usize) -> Script {
+    two_adic31::ext4_roll(offset)
+}
@@ -1,6 +1,141 @@
+use crate::treepp::pushable::{Builder, Pushable};
 use crate::treepp::*;
+use core::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+use p3_field::PrimeField32;
+use p3_mersenne_31::Mersenne31 as P3M31;
+use rand::distributions::{Distribution, Standard};
 
-const MOD: u32 = (1 << 31) - 1;
+pub(crate) const MOD: u32 = (1 << 31) - 1;
+
+/// An element of the field M31, i.e., the integers modulo p = 2^31 - 1.
+///
+/// The value is always kept in canonical form, in [0, p), and is pushed to the stack as a
+/// single element, matching the input of the m31 gadgets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct M31(u32);
+
+impl M31 {
+    pub const ZERO: M31 = M31(0);
+    pub const ONE: M31 = M31(1);
+
+    /// Reduces `value` modulo p.
+    pub const fn new(value: u32) -> M31 {
+        M31(value % MOD)
+    }
+
+    /// The canonical value in [0, p).
+    pub const fn value(&self) -> u32 {
+        self.0
+    }
+
+    pub fn double(&self) -> M31 {
+        *self + *self
+    }
+
+    pub fn square(&self) -> M31 {
+        *self * *self
+    }
+
+    pub fn pow(&self, mut e: u128) -> M31 {
+        let mut res = M31::ONE;
+        let mut base = *self;
+        while e > 0 {
+            if e & 1 == 1 {
+                res *= base;
+            }
+            base = base.square();
+            e >>= 1;
+        }
+        res
+    }
+
+    /// The inverse, computed as a^(p - 2), so that zero is mapped to zero as in `m31_inverse`.
+    pub fn inverse(&self) -> M31 {
+        self.pow((MOD - 2) as u128)
+    }
+}
+
+impl From<u32> for M31 {
+    fn from(value: u32) -> M31 {
+        M31::new(value)
+    }
+}
+
+impl From<P3M31> for M31 {
+    fn from(value: P3M31) -> M31 {
+        M31(value.as_canonical_u32())
+    }
+}
+
+impl Add for M31 {
+    type Output = M31;
+
+    fn add(self, rhs: M31) -> M31 {
+        M31::new(self.0 + rhs.0)
+    }
+}
+
+impl Sub for M31 {
+    type Output = M31;
+
+    fn sub(self, rhs: M31) -> M31 {
+        M31::new(self.0 + MOD - rhs.0)
+    }
+}
+
+impl Neg for M31 {
+    type Output = M31;
+
+    fn neg(self) -> M31 {
+        M31::ZERO - self
+    }
+}
+
+impl Mul for M31 {
+    type Output = M31;
+
+    fn mul(self, rhs: M31) -> M31 {
+        M31(((self.0 as u64 * rhs.0 as u64) % MOD as u64) as u32)
+    }
+}
+
+impl Div for M31 {
+    type Output = M31;
+
+    fn div(self, rhs: M31) -> M31 {
+        self * rhs.inverse()
+    }
+}
+
+impl AddAssign for M31 {
+    fn add_assign(&mut self, rhs: M31) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for M31 {
+    fn sub_assign(&mut self, rhs: M31) {
+        *self = *self - rhs;
+    }
+}
+
+impl MulAssign for M31 {
+    fn mul_assign(&mut self, rhs: M31) {
+        *self = *self * rhs;
+    }
+}
+
+impl Distribution<M31> for Standard {
+    fn sample<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> M31 {
+        M31::new(rng.gen_range(0..MOD))
+    }
+}
+
+impl Pushable for M31 {
+    fn bitcoin_script_push(self, builder: Builder) -> Builder {
+        self.0.bitcoin_script_push(builder)
+    }
+}
 
 pub fn m31_to_n31() -> Script {
     script! {
@@ -214,9 +349,562 @@ pub fn m31_mul() -> Script {
     }
 }
 
+pub fn m31_square() -> Script {
+    script! {
+        // idea:
+        // - split a into a_h and a_l where a = a_h * 2^16 + a_l, a_h is in [0, 2^15], and a_l is in [-2^15, 2^15)
+        //
+        // a^2 = a_h^2 * 2^32 + a_h * a_l * 2^17 + a_l^2
+        //     = a_h^2 * 2 + a_h * a_l * 2^17 + a_l^2
+        //
+        // here, a_h^2 and a_l^2 are both at most 2^30, and |a_h * a_l| is also at most 2^30,
+        // so none of the three products overflows.
+        //
+        // a_h is computed as floor((a + 2^15) / 2^16) = floor((floor(a / 2) + 2^14) / 2^15)
+        // to avoid overflowing a + 2^15.
+        //
+        // split (a_h * a_l mod p) into c_h * 2^14 + c_l where c_h is the higher 17 bits and c_l is the lower 14 bits
+        //
+        // (c_h * 2^14 + c_l) * 2^17 = c_h + c_l * 2^17
+
+        // split `a`
+        OP_DUP
+        2 OP_DIV
+        { 1 << 14 } OP_ADD
+        { 1 << 15 } OP_DIV
+        OP_TUCK
+        { 1 << 15 } OP_MUL OP_SUB
+        OP_OVER
+        { 1 << 15 } OP_MUL OP_SUB
+        // current stack: a_h a_l
+
+        // compute a_h^2 * 2
+        OP_OVER OP_DUP OP_MUL
+        m31_double OP_TOALTSTACK
+
+        // compute a_l^2
+        OP_DUP OP_DUP OP_MUL OP_TOALTSTACK
+
+        // compute c = a_h * a_l mod p
+        OP_MUL
+        m31_adjust
+
+        // split c = c_h * 2^14 + c_l
+        OP_DUP
+        { 1 << 14 } OP_DIV
+        OP_DUP
+        { 1 << 14 } OP_MUL
+        OP_ROT OP_SWAP OP_SUB
+
+        // stack: c_h c_l
+        // altstack: a_h^2 * 2   a_l^2
+
+        { 1 << 17 } OP_MUL
+        m31_add
+        OP_FROMALTSTACK
+        m31_add
+        OP_FROMALTSTACK
+        m31_add
+    }
+}
+
+pub fn m31_inverse_with_hint() -> Script {
+    // input stack: a a_inv
+    // where a_inv is provided by the prover as a hint
+    //
+    // the script checks that a_inv is a valid m31 element and that a * a_inv = 1,
+    // which also rejects a = 0 since 0 * a_inv = 0
+    script! {
+        OP_DUP
+        0 { MOD } OP_WITHIN OP_VERIFY
+        OP_TUCK
+        m31_mul
+        1 OP_EQUALVERIFY
+    }
+}
+
+pub fn m31_inverse() -> Script {
+    // compute a^(p - 2) = a^(2^31 - 3) with the addition chain
+    //
+    // a^(2^2 - 1) = a^2 * a
+    // a^(2^4 - 1) = (a^(2^2 - 1))^(2^2) * a^(2^2 - 1)
+    // a^(2^8 - 1) = (a^(2^4 - 1))^(2^4) * a^(2^4 - 1)
+    // a^(2^16 - 1) = (a^(2^8 - 1))^(2^8) * a^(2^8 - 1)
+    // a^(2^24 - 1) = (a^(2^16 - 1))^(2^8) * a^(2^8 - 1)
+    // a^(2^28 - 1) = (a^(2^24 - 1))^(2^4) * a^(2^4 - 1)
+    // a^(2^29 - 1) = (a^(2^28 - 1))^2 * a
+    // a^(2^31 - 3) = (a^(2^29 - 1))^(2^2) * a
+    //
+    // note: zero is mapped to zero
+    script! {
+        OP_DUP m31_square
+        OP_OVER m31_mul
+        // current stack: a a^(2^2 - 1)
+
+        OP_DUP
+        for _ in 0..2 {
+            m31_square
+        }
+        OP_OVER m31_mul
+        // current stack: a a^(2^2 - 1) a^(2^4 - 1)
+
+        OP_DUP
+        for _ in 0..4 {
+            m31_square
+        }
+        OP_OVER m31_mul
+        // current stack: a a^(2^2 - 1) a^(2^4 - 1) a^(2^8 - 1)
+
+        OP_DUP
+        for _ in 0..8 {
+            m31_square
+        }
+        OP_OVER m31_mul
+        // current stack: a a^(2^2 - 1) a^(2^4 - 1) a^(2^8 - 1) a^(2^16 - 1)
+
+        for _ in 0..8 {
+            m31_square
+        }
+        m31_mul
+        // current stack: a a^(2^2 - 1) a^(2^4 - 1) a^(2^24 - 1)
+
+        for _ in 0..4 {
+            m31_square
+        }
+        m31_mul
+        OP_NIP
+        // current stack: a a^(2^28 - 1)
+
+        m31_square
+        OP_OVER m31_mul
+        // current stack: a a^(2^29 - 1)
+
+        for _ in 0..2 {
+            m31_square
+        }
+        m31_mul
+    }
+}
+
+pub fn m31_batch_inverse(n: usize) -> Script {
+    // input stack: a_0 a_1 ... a_{n - 1}
+    // output stack: a_0^-1 a_1^-1 ... a_{n - 1}^-1
+    //
+    // Montgomery's trick with a single m31_inverse, see qm31_batch_inverse
+    //
+    // note: all the elements must be nonzero, otherwise every output is zero
+    if n == 0 {
+        return script! {};
+    }
+
+    script! {
+        for _ in 1..n {
+            OP_TOALTSTACK
+        }
+
+        // compute the prefix products
+        for _ in 1..n {
+            OP_FROMALTSTACK
+            OP_2DUP
+            m31_mul
+        }
+
+        m31_inverse
+
+        for _ in 1..n {
+            OP_DUP
+            3 OP_ROLL
+            m31_mul
+            OP_TOALTSTACK
+            m31_mul
+        }
+
+        for _ in 1..n {
+            OP_FROMALTSTACK
+        }
+    }
+}
+
+pub fn m31_batch_inverse_with_hint(n: usize) -> Script {
+    // input stack: a_0 ... a_{n - 1} b_0 ... b_{n - 1} r
+    // where b_0, ..., b_{n - 1} are provided by the prover as hints, and r is a random
+    // challenge that the prover cannot predict when choosing the hints, e.g., a Fiat-Shamir
+    // challenge drawn after the hints are committed
+    //
+    // output stack: b_0 ... b_{n - 1}
+    //
+    // the script checks that every b_i is a valid m31 element and, with a single randomised
+    // product check, that
+    //
+    // (r - a_0 * b_0) * ... * (r - a_{n - 1} * b_{n - 1}) = (r - 1)^n
+    //
+    // both sides are polynomials of degree n in r, which are equal only if every a_i * b_i is 1,
+    // so a wrong hint passes with probability at most n / p
+    script! {
+        1
+        // current stack after each step: a_0 ... a_i b_0 ... b_i r acc
+        for i in (0..n).rev() {
+            2 OP_PICK
+            0 { MOD } OP_WITHIN OP_VERIFY
+
+            { i + 3 } OP_ROLL
+            3 OP_PICK
+            m31_mul
+            2 OP_PICK
+            OP_SWAP
+            m31_sub
+            m31_mul
+
+            2 OP_ROLL
+            OP_TOALTSTACK
+        }
+
+        OP_SWAP
+        1 m31_sub
+        { m31_pow_const(n as u32) }
+        OP_EQUALVERIFY
+
+        for _ in 0..n {
+            OP_FROMALTSTACK
+        }
+    }
+}
+
+// sliding-window decomposition of `e`: the first odd window, followed by
+// steps of (number of squarings, odd multiplier or 0 for none)
+fn m31_pow_windows(e: u32, w: u32) -> (u32, Vec<(u32, u32)>) {
+    let bits = 32 - e.leading_zeros();
+
+    let mut first = None;
+    let mut steps = vec![];
+    let mut squarings = 0;
+
+    let mut i = bits as i64 - 1;
+    while i >= 0 {
+        if (e >> i) & 1 == 0 {
+            squarings += 1;
+            i -= 1;
+            continue;
+        }
+
+        // find the longest window of at most w bits starting at bit i and ending in a one
+        let mut l = (w as i64).min(i + 1);
+        while (e >> (i - l + 1)) & 1 == 0 {
+            l -= 1;
+        }
+        let value = (e >> (i - l + 1)) & ((1 << l) - 1);
+
+        if first.is_none() {
+            first = Some(value);
+        } else {
+            steps.push((squarings + l as u32, value));
+        }
+        squarings = 0;
+        i -= l;
+    }
+    if squarings > 0 {
+        steps.push((squarings, 0));
+    }
+
+    (first.unwrap(), steps)
+}
+
+pub fn m31_pow_const(e: u32) -> Script {
+    // input stack: a
+    // output stack: a^e
+    if e == 0 {
+        return script! {
+            OP_DROP 1
+        };
+    }
+
+    if e == 1 {
+        return script! {};
+    }
+
+    // the table of odd powers a, a^3, ..., a^(2 * t - 1) that the windows refer to
+    let table_size = |first: u32, steps: &[(u32, u32)]| {
+        (steps
+            .iter()
+            .map(|step| step.1)
+            .chain([first])
+            .max()
+            .unwrap()
+            / 2
+            + 1) as usize
+    };
+
+    // pick the window size that minimizes the number of multiplications and squarings
+    let (first, steps) = (1..=5)
+        .map(|w| m31_pow_windows(e, w))
+        .min_by_key(|(first, steps)| {
+            let t = table_size(*first, steps);
+            let precompute = if t > 1 { t } else { 0 };
+            precompute
+                + steps
+                    .iter()
+                    .map(|step| step.0 as usize + (step.1 != 0) as usize)
+                    .sum::<usize>()
+        })
+        .unwrap();
+
+    let t = table_size(first, &steps);
+    let depth = |v: u32| t - 1 - (v as usize / 2);
+
+    script! {
+        if t > 1 {
+            OP_DUP m31_square
+            for _ in 1..t {
+                OP_DUP 2 OP_PICK m31_mul OP_SWAP
+            }
+            OP_DROP
+        }
+        // current stack: a a^3 ... a^(2 * t - 1)
+
+        { depth(first) } OP_PICK
+        for step in steps.iter() {
+            for _ in 0..step.0 {
+                m31_square
+            }
+            if step.1 != 0 {
+                { depth(step.1) + 1 } OP_PICK m31_mul
+            }
+        }
+
+        for _ in 0..t {
+            OP_NIP
+        }
+    }
+}
+
+pub fn m31_pow_var() -> Script {
+    // input stack: a e
+    // where e is an exponent in [0, 2^31)
+    //
+    // output stack: a^e
+    script! {
+        m31_to_bits
+        31 OP_ROLL
+        // current stack: b30 b29 ... b0 a
+
+        31 OP_ROLL
+        OP_IF OP_DUP OP_ELSE 1 OP_ENDIF
+        // current stack: b29 ... b0 a r
+
+        for i in (0..30).rev() {
+            m31_square
+            { i + 2 } OP_ROLL
+            OP_IF OP_OVER m31_mul OP_ENDIF
+        }
+        OP_NIP
+    }
+}
+
+pub fn m31_is_square() -> Script {
+    // input stack: a
+    // output stack: 1 if a is a square (including 0), 0 otherwise
+    //
+    // Euler's criterion: a^((p - 1) / 2) is 0, 1 or p - 1, so a is a square if and only if
+    // a^((p + 1) / 2) = a, and a^((p + 1) / 2) = a^(2^30) is cheaper to compute
+    script! {
+        OP_DUP
+        { m31_pow_const((MOD + 1) / 2) }
+        OP_EQUAL
+    }
+}
+
+pub fn m31_sqrt() -> Script {
+    // input stack: a
+    // output stack: r such that r^2 = a
+    //
+    // since p = 3 (mod 4), r = a^((p + 1) / 4) = a^(2^29) is a square root of a whenever
+    // a is a square, and r is the root that is itself a square
+    //
+    // the script fails if a is not a square, in which case r^2 = -a
+    script! {
+        OP_DUP
+        { m31_pow_const((MOD + 1) / 4) }
+        OP_DUP m31_square
+        OP_ROT OP_EQUALVERIFY
+    }
+}
+
+pub fn m31_sqrt_with_hint() -> Script {
+    // input stack: a r
+    // where r is provided by the prover as a hint
+    //
+    // the script checks that r is a valid m31 element and that r^2 = a,
+    // both r and -r are accepted
+    //
+    // output stack: r
+    script! {
+        OP_DUP
+        0 { MOD } OP_WITHIN OP_VERIFY
+        OP_TUCK
+        m31_square
+        OP_EQUALVERIFY
+    }
+}
+
+// multiply an m31 element by 2^k, for k in 0..31
+//
+// split a = a_h * 2^(31 - k) + a_l, then a * 2^k = a_h * 2^31 + a_l * 2^k = a_h + a_l * 2^k,
+// where the sum is at most 2^31 - 2 and therefore needs no modular reduction
+fn m31_shift(k: u32) -> Script {
+    if k == 0 {
+        return script! {};
+    }
+
+    script! {
+        OP_DUP
+        { 1 << (31 - k) } OP_DIV
+        OP_DUP
+        { 1 << (31 - k) } OP_MUL
+        OP_ROT OP_SWAP OP_SUB
+        { 1 << k } OP_MUL
+        OP_ADD
+    }
+}
+
+pub fn m31_mul_by_constant(c: u32) -> Script {
+    let c = c % MOD;
+
+    if c == 0 {
+        return script! {
+            OP_DROP 0
+        };
+    }
+
+    // multiplying by -c and negating the result is sometimes cheaper
+    let direct = m31_mul_by_constant_positive(c);
+    let negated = script! {
+        { m31_mul_by_constant_positive(MOD - c) }
+        0 OP_SWAP m31_sub
+    };
+
+    if negated.len() < direct.len() {
+        negated
+    } else {
+        direct
+    }
+}
+
+fn m31_mul_by_constant_positive(c: u32) -> Script {
+    if c == 1 {
+        return script! {};
+    }
+
+    let bits = 32 - c.leading_zeros();
+    let ones = (0..bits)
+        .filter(|i| (c >> i) & 1 == 1)
+        .collect::<Vec<u32>>();
+
+    let mut candidates = vec![];
+
+    // shift-and-add: double-and-add over the bits of c, from the most significant bit
+    candidates.push(script! {
+        OP_DUP
+        for i in (0..bits - 1).rev() {
+            m31_double
+            if (c >> i) & 1 == 1 {
+                OP_OVER m31_add
+            }
+        }
+        OP_NIP
+    });
+
+    // sparse constants: sum of a * 2^k over the bits of c
+    candidates.push(script! {
+        for k in ones[1..].iter() {
+            OP_DUP { m31_shift(*k) } OP_SWAP
+        }
+        { m31_shift(ones[0]) }
+        for _ in 1..ones.len() {
+            m31_add
+        }
+    });
+
+    // small constants: c < 2^m with m <= 15
+    //
+    // split a = a_h * 2^(31 - m) + a_l, so that both a_h * c and a_l * c are below 2^31
+    //
+    // a * c = a_h * c * 2^(31 - m) + a_l * c
+    if bits <= 15 {
+        candidates.push(script! {
+            OP_DUP
+            { 1 << (31 - bits) } OP_DIV
+            OP_DUP
+            { 1 << (31 - bits) } OP_MUL
+            OP_ROT OP_SWAP OP_SUB
+            // current stack: a_h a_l
+
+            { c } OP_MUL
+            OP_SWAP
+            { c } OP_MUL
+            { m31_shift(31 - bits) }
+            m31_add
+        });
+    }
+
+    // general constants: a precomputed split of c
+    //
+    // split a = a_h * 2^15 + a_l where a_h is the higher 16 bits and a_l is the lower 15 bits
+    // split c = c_h * 2^16 + c_l where c_h is in [0, 2^15] and c_l is in [-2^15, 2^15)
+    //
+    // a * c = a_h * c_h * 2^31 + (a_h * c_l + a_l * c_h * 2) * 2^15 + a_l * c_l
+    //       = a_h * c_h + (a_h * c_l + a_l * c_h * 2) * 2^15 + a_l * c_l
+    //
+    // all the products fit below 2^31 in absolute value, and the sign of c_l is known
+    // at compile time, so it is handled by choosing between m31_add and m31_sub
+    let c_h = (c + (1 << 15)) >> 16;
+    let c_l = c as i64 - ((c_h as i64) << 16);
+    let c_l_abs = c_l.unsigned_abs() as u32;
+    candidates.push(script! {
+        OP_DUP
+        { 1 << 15 } OP_DIV
+        OP_DUP
+        { 1 << 15 } OP_MUL
+        OP_ROT OP_SWAP OP_SUB
+        // current stack: a_h a_l
+
+        // compute a_h * c_h
+        OP_OVER { c_h } OP_MUL OP_TOALTSTACK
+
+        // compute a_l * |c_l|
+        OP_DUP { c_l_abs } OP_MUL OP_TOALTSTACK
+
+        // compute (a_l * c_h * 2 + a_h * c_l) * 2^15
+        { c_h * 2 } OP_MUL
+        OP_SWAP { c_l_abs } OP_MUL
+        if c_l >= 0 {
+            m31_add
+        } else {
+            m31_sub
+        }
+        { m31_shift(15) }
+
+        OP_FROMALTSTACK
+        if c_l >= 0 {
+            m31_add
+        } else {
+            m31_sub
+        }
+        OP_FROMALTSTACK
+        m31_add
+    });
+
+    candidates
+        .into_iter()
+        .min_by_key(|script| script.len())
+        .unwrap()
+}
+
 #[cfg(test)]
 mod test {
+    use crate::exec::execute_script;
     use crate::treepp::*;
+    use p3_field::{AbstractField, Field, PrimeField32};
+    use p3_mersenne_31::Mersenne31 as P3M31;
     use rand::{Rng, SeedableRng};
     use rand_chacha::ChaCha20Rng;
 
@@ -347,4 +1035,331 @@ mod test {
             assert!(exec_result.success);
         }
     }
+
+    #[test]
+    fn test_m31_inverse() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!("m31 inverse: {}", m31_inverse().len());
+
+        for _ in 0..20 {
+            let a: P3M31 = prng.gen();
+            let a_inv = a.inverse();
+
+            let script = script! {
+                { a.as_canonical_u32() }
+                m31_inverse
+                { a_inv.as_canonical_u32() }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_m31_inverse_with_hint() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!("m31 inverse with hint: {}", m31_inverse_with_hint().len());
+
+        for _ in 0..100 {
+            let a: P3M31 = prng.gen();
+            let a_inv = a.inverse();
+
+            let script = script! {
+                { a.as_canonical_u32() }
+                { a_inv.as_canonical_u32() }
+                m31_inverse_with_hint
+                { a_inv.as_canonical_u32() }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+
+            let wrong = a_inv + P3M31::one();
+            let script = script! {
+                { a.as_canonical_u32() }
+                { wrong.as_canonical_u32() }
+                m31_inverse_with_hint
+                { wrong.as_canonical_u32() }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(!exec_result.success);
+        }
+
+        // zero has no inverse
+        let script = script! {
+            0 0
+            m31_inverse_with_hint
+            0 OP_EQUAL
+        };
+        let exec_result = execute_script(script);
+        assert!(!exec_result.success);
+    }
+
+    #[test]
+    fn test_m31_sqrt() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!("m31 is_square: {}", m31_is_square().len());
+        eprintln!("m31 sqrt: {}", m31_sqrt().len());
+        eprintln!("m31 sqrt with hint: {}", m31_sqrt_with_hint().len());
+
+        for _ in 0..10 {
+            let a: P3M31 = prng.gen();
+            let is_square = a.exp_u64(((MOD - 1) / 2) as u64) == P3M31::one();
+
+            let script = script! {
+                { a.as_canonical_u32() }
+                m31_is_square
+                { is_square as u32 }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+
+            let r = a.exp_u64(((MOD + 1) / 4) as u64);
+            let script = script! {
+                { a.as_canonical_u32() }
+                m31_sqrt
+                { r.as_canonical_u32() }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert_eq!(exec_result.success, is_square);
+
+            let b = a.square();
+            let script = script! {
+                { b.as_canonical_u32() }
+                { a.as_canonical_u32() }
+                m31_sqrt_with_hint
+                { a.as_canonical_u32() }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+
+            let wrong = a + P3M31::one();
+            let script = script! {
+                { b.as_canonical_u32() }
+                { wrong.as_canonical_u32() }
+                m31_sqrt_with_hint
+                { wrong.as_canonical_u32() }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(!exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_m31_batch_inverse() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!("m31 batch inverse (n = 8): {}", m31_batch_inverse(8).len());
+        eprintln!(
+            "m31 batch inverse with hint (n = 8): {}",
+            m31_batch_inverse_with_hint(8).len()
+        );
+
+        for n in 1..6 {
+            let a: Vec<P3M31> = (0..n).map(|_| prng.gen()).collect();
+            let a_inv: Vec<P3M31> = a.iter().map(|v| v.inverse()).collect();
+            let r: P3M31 = prng.gen();
+
+            let script = script! {
+                for v in a.iter() {
+                    { v.as_canonical_u32() }
+                }
+                { m31_batch_inverse(n) }
+                for v in a_inv.iter().rev() {
+                    { v.as_canonical_u32() }
+                    OP_EQUALVERIFY
+                }
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+
+            let script = script! {
+                for v in a.iter() {
+                    { v.as_canonical_u32() }
+                }
+                for v in a_inv.iter() {
+                    { v.as_canonical_u32() }
+                }
+                { r.as_canonical_u32() }
+                { m31_batch_inverse_with_hint(n) }
+                for v in a_inv.iter().rev() {
+                    { v.as_canonical_u32() }
+                    OP_EQUALVERIFY
+                }
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+
+            let script = script! {
+                for v in a.iter() {
+                    { v.as_canonical_u32() }
+                }
+                for v in a.iter() {
+                    { v.as_canonical_u32() }
+                }
+                { r.as_canonical_u32() }
+                { m31_batch_inverse_with_hint(n) }
+                for _ in 0..n {
+                    OP_DROP
+                }
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(!exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_m31_native() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+
+        for _ in 0..100 {
+            let a: P3M31 = prng.gen();
+            let b: P3M31 = prng.gen();
+
+            let a_native = M31::from(a);
+            let b_native = M31::from(b);
+
+            assert_eq!(M31::from(a + b), a_native + b_native);
+            assert_eq!(M31::from(a - b), a_native - b_native);
+            assert_eq!(M31::from(a * b), a_native * b_native);
+            assert_eq!(M31::from(a.inverse()), a_native.inverse());
+
+            let script = script! {
+                { a_native }
+                { b_native }
+                m31_mul
+                { a_native * b_native }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_m31_pow_const() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+
+        let mut exponents = vec![0u32, 1, 2, 3, 1 << 20, (1 << 31) - 3, u32::MAX];
+        for _ in 0..10 {
+            exponents.push(prng.gen());
+        }
+
+        for e in exponents {
+            let pow_script = m31_pow_const(e);
+            eprintln!("m31 pow_const({}): {}", e, pow_script.len());
+
+            let a: P3M31 = prng.gen();
+            let c = a.exp_u64(e as u64);
+
+            let script = script! {
+                { a.as_canonical_u32() }
+                { pow_script.clone() }
+                { c.as_canonical_u32() }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_m31_pow_var() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!("m31 pow_var: {}", m31_pow_var().len());
+
+        for i in 0..20 {
+            let e: u32 = match i {
+                0 => 0,
+                1 => 1,
+                2 => MOD,
+                _ => prng.gen::<u32>() >> 1,
+            };
+
+            let a: P3M31 = prng.gen();
+            let c = a.exp_u64(e as u64);
+
+            let script = script! {
+                { a.as_canonical_u32() }
+                { e }
+                m31_pow_var
+                { c.as_canonical_u32() }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_m31_square() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!("m31 square: {}", m31_square().len());
+        eprintln!("m31 dup mul: {}", script! { OP_DUP m31_mul }.len());
+
+        let mut values = vec![
+            0,
+            1,
+            (1 << 15) - 1,
+            1 << 15,
+            (1 << 16) - 1,
+            1 << 16,
+            MOD - (1 << 15),
+            MOD - 1,
+        ];
+        for _ in 0..100 {
+            values.push(prng.gen::<u32>() % MOD);
+        }
+
+        for a_m31 in values {
+            let square_m31 = (((a_m31 as u64) * (a_m31 as u64)) % (MOD as u64)) as u32;
+
+            let script = script! {
+                { a_m31 }
+                m31_square
+                { square_m31 }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_m31_mul_by_constant() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+
+        let mut constants = vec![0, 1, 2, 3, 255, 1 << 15, (1 << 16) - 1, 1 << 20, MOD - 1];
+        for _ in 0..20 {
+            constants.push(prng.gen::<u32>() % MOD);
+        }
+
+        for c in constants {
+            let mul_script = m31_mul_by_constant(c);
+            eprintln!("m31 mul_by_constant({}): {}", c, mul_script.len());
+
+            for _ in 0..10 {
+                let a: u32 = prng.gen();
+                let a_m31 = a % MOD;
+                let prod_m31 = (((a_m31 as u64) * (c as u64)) % (MOD as u64)) as u32;
+
+                let script = script! {
+                    { a_m31 }
+                    { mul_script.clone() }
+                    { prod_m31 }
+                    OP_EQUAL
+                };
+                let exec_result = execute_script(script);
+                assert!(exec_result.success);
+            }
+        }
+    }
 }
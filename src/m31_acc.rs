@@ -0,0 +1,386 @@
+use crate::m31::m31_add;
+use crate::treepp::*;
+
+// An unreduced accumulator is kept on the stack as three non-negative limbs
+//
+//      l2 l1 l0
+//
+// representing l0 + l1 * 2^11 + l2 * 2^22 (mod p).
+//
+// The limbs are only bounded, not reduced, so that sums of many products can be added
+// with a plain OP_ADD per limb. `M31Acc` keeps track of the bounds at compile time so that
+// the limbs are normalized only when the next addition could leave the script number range.
+
+/// The largest limb bound an accumulator is allowed to reach, which leaves enough headroom for
+/// the carries in `m31_acc_normalize`.
+pub const M31_ACC_LIMIT: u32 = 1 << 30;
+
+/// Compile-time bounds of an unreduced accumulator on the stack.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct M31Acc {
+    /// `bounds[i]` is a strict upper bound of the limb with coefficient 2^(11 * i).
+    pub bounds: [u32; 3],
+}
+
+impl M31Acc {
+    /// The bounds of the output of `m31_to_acc`.
+    pub const ELEMENT: M31Acc = M31Acc {
+        bounds: [1 << 11, 1 << 11, 1 << 9],
+    };
+
+    /// The bounds of the output of `m31_mul_unreduced`.
+    pub const MUL: M31Acc = M31Acc {
+        bounds: [3 << 22, (1 << 23) + (1 << 20), 3 << 21],
+    };
+
+    /// The bounds after adding `other` limb by limb.
+    pub fn add_bounds(&self, other: &M31Acc) -> M31Acc {
+        M31Acc {
+            bounds: [
+                self.bounds[0] + other.bounds[0] - 1,
+                self.bounds[1] + other.bounds[1] - 1,
+                self.bounds[2] + other.bounds[2] - 1,
+            ],
+        }
+    }
+
+    /// The bounds after `m31_acc_normalize`.
+    pub fn normalize_bounds(&self) -> M31Acc {
+        let c0 = (self.bounds[0] - 1) >> 11;
+        let c1 = (self.bounds[1] - 1 + c0) >> 11;
+        let c2 = (self.bounds[2] - 1 + c1) >> 9;
+
+        M31Acc {
+            bounds: [
+                (self.bounds[0] - 1).min((1 << 11) - 1) + c2 + 1,
+                (self.bounds[1] + c0).min(1 << 11),
+                (self.bounds[2] + c1).min(1 << 9),
+            ],
+        }
+    }
+
+    /// Whether the limbs can be combined without normalizing first.
+    pub fn is_normalized(&self) -> bool {
+        self.bounds[0] <= 1 << 22 && self.bounds[1] <= 1 << 11 && self.bounds[2] <= 1 << 9
+    }
+
+    fn fits(&self) -> bool {
+        self.bounds.iter().all(|bound| *bound <= M31_ACC_LIMIT)
+    }
+}
+
+// split an m31 element into three limbs
+pub fn m31_to_acc() -> Script {
+    script! {
+        OP_DUP
+        { 1 << 22 } OP_DIV
+        OP_SWAP OP_OVER
+        { 1 << 22 } OP_MUL OP_SUB
+        OP_DUP
+        { 1 << 11 } OP_DIV
+        OP_SWAP OP_OVER
+        { 1 << 11 } OP_MUL OP_SUB
+    }
+}
+
+pub fn m31_mul_unreduced() -> Script {
+    // idea:
+    // - split a and b into limbs a = a2 * 2^22 + a1 * 2^11 + a0, b = b2 * 2^22 + b1 * 2^11 + b0
+    //   where a0, a1, b0, b1 are 11 bits and a2, b2 are 9 bits
+    //
+    // since 2^33 = 4 and 2^44 = 4 * 2^11 (mod p),
+    //
+    // a * b = (a0 * b0 + 4 * (a1 * b2 + a2 * b1))
+    //       + (a0 * b1 + a1 * b0 + 4 * a2 * b2) * 2^11
+    //       + (a0 * b2 + a1 * b1 + a2 * b0) * 2^22
+    //
+    // where every limb is below 2^24, see `M31Acc::MUL`.
+    script! {
+        OP_TOALTSTACK
+        m31_to_acc
+        OP_FROMALTSTACK
+        m31_to_acc
+        // current stack: a2 a1 a0 b2 b1 b0
+
+        // compute l2 = a0 * b2 + a1 * b1 + a2 * b0
+        3 OP_PICK 3 OP_PICK OP_MUL
+        5 OP_PICK 3 OP_PICK OP_MUL OP_ADD
+        6 OP_PICK 2 OP_PICK OP_MUL OP_ADD
+        OP_TOALTSTACK
+
+        // compute l1 = a0 * b1 + a1 * b0 + 4 * a2 * b2
+        3 OP_PICK 2 OP_PICK OP_MUL
+        5 OP_PICK 2 OP_PICK OP_MUL OP_ADD
+        6 OP_PICK 4 OP_PICK OP_MUL 4 OP_MUL OP_ADD
+        OP_TOALTSTACK
+
+        // compute l0 = a0 * b0 + 4 * (a1 * b2 + a2 * b1)
+        4 OP_ROLL 3 OP_ROLL OP_MUL
+        4 OP_ROLL 3 OP_ROLL OP_MUL
+        OP_ADD 4 OP_MUL
+        OP_ROT OP_ROT OP_MUL OP_ADD
+
+        OP_FROMALTSTACK OP_FROMALTSTACK
+        OP_SWAP OP_ROT
+    }
+}
+
+// propagate the carries between the limbs, see `M31Acc::normalize_bounds` for the resulting bounds
+pub fn m31_acc_normalize() -> Script {
+    script! {
+        OP_DUP
+        { 1 << 11 } OP_DIV
+        OP_DUP
+        { 1 << 11 } OP_MUL
+        OP_ROT OP_SWAP OP_SUB
+        OP_TOALTSTACK OP_ADD
+
+        OP_DUP
+        { 1 << 11 } OP_DIV
+        OP_DUP
+        { 1 << 11 } OP_MUL
+        OP_ROT OP_SWAP OP_SUB
+        OP_TOALTSTACK OP_ADD
+
+        // the carry out of l2 has coefficient 2^31 = 1
+        OP_DUP
+        { 1 << 9 } OP_DIV
+        OP_DUP
+        { 1 << 9 } OP_MUL
+        OP_ROT OP_SWAP OP_SUB
+        OP_SWAP
+        OP_FROMALTSTACK OP_SWAP
+        OP_FROMALTSTACK OP_ADD
+    }
+}
+
+// Input: two accumulators with bounds `lhs` and `rhs`, the latter on top
+// Output: their limb-wise sum, together with its bounds
+pub fn m31_acc_add(lhs: M31Acc, rhs: M31Acc) -> (Script, M31Acc) {
+    let mut lhs = lhs;
+    let mut rhs = rhs;
+
+    let normalize_rhs = !lhs.add_bounds(&rhs).fits();
+    if normalize_rhs {
+        rhs = rhs.normalize_bounds();
+    }
+
+    let normalize_lhs = !lhs.add_bounds(&rhs).fits();
+    if normalize_lhs {
+        lhs = lhs.normalize_bounds();
+    }
+
+    let script = script! {
+        if normalize_rhs {
+            m31_acc_normalize
+        }
+        if normalize_lhs {
+            OP_TOALTSTACK OP_TOALTSTACK OP_TOALTSTACK
+            m31_acc_normalize
+            OP_FROMALTSTACK OP_FROMALTSTACK OP_FROMALTSTACK
+        }
+        3 OP_ROLL OP_ADD OP_TOALTSTACK
+        2 OP_ROLL OP_ADD OP_TOALTSTACK
+        OP_ADD
+        OP_FROMALTSTACK OP_FROMALTSTACK
+    };
+
+    (script, lhs.add_bounds(&rhs))
+}
+
+// Input: an accumulator with bounds `acc`
+// Output: the canonical m31 element it represents
+pub fn m31_acc_reduce(acc: M31Acc) -> Script {
+    // after normalization, l2 * 2^22 + l1 * 2^11 is at most 2^31 - 2^11 and l0 is below 2^22,
+    // so a single modular addition suffices
+    let acc_normalized = if acc.is_normalized() {
+        acc
+    } else {
+        acc.normalize_bounds()
+    };
+    assert!(acc_normalized.is_normalized());
+
+    script! {
+        if !acc.is_normalized() {
+            m31_acc_normalize
+        }
+        OP_TOALTSTACK
+        OP_SWAP
+        { 1 << 11 } OP_MUL
+        OP_ADD
+        { 1 << 11 } OP_MUL
+        OP_FROMALTSTACK
+        m31_add
+    }
+}
+
+// Input: a_0 ... a_{n-1} b_0 ... b_{n-1}
+// Output: a_0 * b_0 + ... + a_{n-1} * b_{n-1}
+pub fn m31_inner_product(n: usize) -> Script {
+    if n == 0 {
+        return script! {
+            0
+        };
+    }
+
+    let mut acc = M31Acc::MUL;
+    let mut add_scripts = vec![];
+    for _ in 1..n {
+        let (add_script, new_acc) = m31_acc_add(acc, M31Acc::MUL);
+        add_scripts.push(add_script);
+        acc = new_acc;
+    }
+
+    script! {
+        { n } OP_ROLL
+        m31_mul_unreduced
+        for i in (0..n - 1).rev() {
+            3 OP_ROLL
+            { i + 4 } OP_ROLL
+            m31_mul_unreduced
+            { add_scripts[n - 2 - i].clone() }
+        }
+        { m31_acc_reduce(acc) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::exec::execute_script;
+    use crate::treepp::*;
+    use crate::{
+        m31_acc_add, m31_acc_reduce, m31_inner_product, m31_mul_unreduced, m31_to_acc, M31Acc,
+    };
+    use p3_field::{AbstractField, PrimeField32};
+    use p3_mersenne_31::Mersenne31 as P3M31;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn test_m31_mul_unreduced() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!("m31 mul_unreduced: {}", m31_mul_unreduced().len());
+        eprintln!("m31 acc_reduce: {}", m31_acc_reduce(M31Acc::MUL).len());
+
+        for _ in 0..100 {
+            let a: P3M31 = prng.gen();
+            let b: P3M31 = prng.gen();
+            let c = a * b;
+
+            let script = script! {
+                { a.as_canonical_u32() }
+                { b.as_canonical_u32() }
+                m31_mul_unreduced
+                { m31_acc_reduce(M31Acc::MUL) }
+                { c.as_canonical_u32() }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_m31_acc_sum_of_products() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+
+        for n in [1, 2, 10, 100, 200] {
+            let a: Vec<P3M31> = (0..n).map(|_| prng.gen()).collect();
+            let b: Vec<P3M31> = (0..n).map(|_| prng.gen()).collect();
+            let c: P3M31 = prng.gen();
+
+            let mut expected = c;
+            for (a, b) in a.iter().zip(b.iter()) {
+                expected += *a * *b;
+            }
+
+            let mut acc = M31Acc::ELEMENT;
+            let mut add_scripts = vec![];
+            for _ in 0..n {
+                let (script, new_acc) = m31_acc_add(acc, M31Acc::MUL);
+                add_scripts.push(script);
+                acc = new_acc;
+            }
+
+            let script = script! {
+                { c.as_canonical_u32() }
+                m31_to_acc
+                for i in 0..n {
+                    { a[i].as_canonical_u32() }
+                    { b[i].as_canonical_u32() }
+                    m31_mul_unreduced
+                    { add_scripts[i].clone() }
+                }
+                { m31_acc_reduce(acc) }
+                { expected.as_canonical_u32() }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_m31_acc_extreme_values() {
+        let max = P3M31::neg_one();
+
+        let mut acc = M31Acc::MUL;
+        let mut add_scripts = vec![];
+        for _ in 0..150 {
+            let (script, new_acc) = m31_acc_add(acc, M31Acc::MUL);
+            add_scripts.push(script);
+            acc = new_acc;
+        }
+
+        let expected = max * max * P3M31::from_canonical_u32(151);
+
+        let script = script! {
+            { max.as_canonical_u32() }
+            { max.as_canonical_u32() }
+            m31_mul_unreduced
+            for add_script in add_scripts.iter() {
+                { max.as_canonical_u32() }
+                { max.as_canonical_u32() }
+                m31_mul_unreduced
+                { add_script.clone() }
+            }
+            { m31_acc_reduce(acc) }
+            { expected.as_canonical_u32() }
+            OP_EQUAL
+        };
+        let exec_result = execute_script(script);
+        assert!(exec_result.success);
+    }
+
+    #[test]
+    fn test_m31_inner_product() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+
+        for n in [1, 2, 5, 40] {
+            let inner_product_script = m31_inner_product(n);
+            eprintln!("m31 inner_product({}): {}", n, inner_product_script.len());
+
+            let a: Vec<P3M31> = (0..n).map(|_| prng.gen()).collect();
+            let b: Vec<P3M31> = (0..n).map(|_| prng.gen()).collect();
+
+            let mut expected = P3M31::zero();
+            for (a, b) in a.iter().zip(b.iter()) {
+                expected += *a * *b;
+            }
+
+            let script = script! {
+                for a in a.iter() {
+                    { a.as_canonical_u32() }
+                }
+                for b in b.iter() {
+                    { b.as_canonical_u32() }
+                }
+                { inner_product_script.clone() }
+                { expected.as_canonical_u32() }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+}
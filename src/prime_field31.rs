@@ -0,0 +1,504 @@
+use crate::m31::{m31_add, m31_double, m31_inverse, m31_mul, m31_square, m31_sub, MOD};
+use crate::treepp::pushable::{Builder, Pushable};
+use crate::treepp::*;
+use core::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+use rand::distributions::{Distribution, Standard};
+
+// The pf31 gadgets work over any prime field F_p with an odd prime p below 2^31, and reduce
+// with `OP_DIV` instead of relying on the shape of p. They are meant for experimenting with
+// fields that have no dedicated gadgets, and are considerably more expensive than these.
+//
+// For p = 2^31 - 1, the gadgets fall back to the optimised m31 ones where available.
+
+const fn is_prime(n: u32) -> bool {
+    if n < 2 {
+        return false;
+    }
+    let mut i = 2;
+    while i * i <= n {
+        if n.is_multiple_of(i) {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// An element of the prime field F_p with p = `P`, an odd prime below 2^31.
+///
+/// The value is always kept in canonical form, in [0, p), and is pushed to the stack as a
+/// single element, matching the input of the pf31 gadgets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PrimeField31<const P: u32>(u32);
+
+impl<const P: u32> PrimeField31<P> {
+    // evaluated when a gadget or an element is instantiated, so that an invalid modulus
+    // fails to compile
+    const CHECK_MODULUS: () = assert!(
+        P > 2 && P < 1 << 31 && is_prime(P),
+        "the modulus must be an odd prime below 2^31"
+    );
+
+    pub const ZERO: Self = PrimeField31(0);
+    pub const ONE: Self = PrimeField31(1);
+
+    /// Reduces `value` modulo p.
+    pub const fn new(value: u32) -> Self {
+        let () = Self::CHECK_MODULUS;
+        PrimeField31(value % P)
+    }
+
+    /// The canonical value in [0, p).
+    pub const fn value(&self) -> u32 {
+        self.0
+    }
+
+    pub fn double(&self) -> Self {
+        *self + *self
+    }
+
+    pub fn square(&self) -> Self {
+        *self * *self
+    }
+
+    pub fn pow(&self, mut e: u128) -> Self {
+        let mut res = Self::ONE;
+        let mut base = *self;
+        while e > 0 {
+            if e & 1 == 1 {
+                res *= base;
+            }
+            base = base.square();
+            e >>= 1;
+        }
+        res
+    }
+
+    /// The inverse, computed as a^(p - 2), so that zero is mapped to zero as in `pf31_inverse`.
+    pub fn inverse(&self) -> Self {
+        self.pow((P - 2) as u128)
+    }
+}
+
+impl<const P: u32> From<u32> for PrimeField31<P> {
+    fn from(value: u32) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<const P: u32> Add for PrimeField31<P> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(((self.0 as u64 + rhs.0 as u64) % P as u64) as u32)
+    }
+}
+
+impl<const P: u32> Sub for PrimeField31<P> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.0 + P - rhs.0)
+    }
+}
+
+impl<const P: u32> Neg for PrimeField31<P> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::ZERO - self
+    }
+}
+
+impl<const P: u32> Mul for PrimeField31<P> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(((self.0 as u64 * rhs.0 as u64) % P as u64) as u32)
+    }
+}
+
+impl<const P: u32> Div for PrimeField31<P> {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        self * rhs.inverse()
+    }
+}
+
+impl<const P: u32> AddAssign for PrimeField31<P> {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl<const P: u32> SubAssign for PrimeField31<P> {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl<const P: u32> MulAssign for PrimeField31<P> {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+
+impl<const P: u32> Distribution<PrimeField31<P>> for Standard {
+    fn sample<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> PrimeField31<P> {
+        PrimeField31::new(rng.gen_range(0..P))
+    }
+}
+
+impl<const P: u32> Pushable for PrimeField31<P> {
+    fn bitcoin_script_push(self, builder: Builder) -> Builder {
+        self.0.bitcoin_script_push(builder)
+    }
+}
+
+// moves an element in (-p, p) into [0, p)
+fn pf31_adjust<const P: u32>() -> Script {
+    let () = PrimeField31::<P>::CHECK_MODULUS;
+    script! {
+        OP_DUP
+        0 OP_LESSTHAN
+        OP_IF { P } OP_ADD OP_ENDIF
+    }
+}
+
+// moves an element in (-p, p) into [-p, 0)
+fn pf31_adjust_negative<const P: u32>() -> Script {
+    script! {
+        OP_DUP
+        0 OP_GREATERTHANOREQUAL
+        OP_IF { P } OP_SUB OP_ENDIF
+    }
+}
+
+// Input: x in (-2^31, 2^31)
+// Output: the remainder of x by p, in (-p, p) and with the sign of x
+fn pf31_reduce<const P: u32>() -> Script {
+    script! {
+        OP_DUP
+        { P } OP_DIV
+        { P } OP_MUL OP_SUB
+    }
+}
+
+// Input: x in [0, p)
+// Output: x_h x_l where x = x_h * 2^16 + x_l, x_h is in [0, 2^15], and x_l is in [-2^15, 2^15)
+fn pf31_split() -> Script {
+    // x_h = floor((x + 2^15) / 2^16) = floor((floor(x / 2) + 2^14) / 2^15), and x_h * 2^16 is
+    // subtracted in two halves, as neither x + 2^15 nor x_h * 2^16 may fit when p is close to 2^31
+    script! {
+        OP_DUP
+        2 OP_DIV
+        { 1 << 14 } OP_ADD
+        { 1 << 15 } OP_DIV
+        OP_TUCK
+        { 1 << 15 } OP_MUL OP_SUB
+        OP_OVER
+        { 1 << 15 } OP_MUL OP_SUB
+    }
+}
+
+// Input: a in [0, p)
+// Output: c * a in [0, p), for c in [1, 2^15]
+fn pf31_mul_by_small_constant<const P: u32>(c: u32) -> Script {
+    // idea:
+    // - let d = ceil(p / c) and e = c * d - p, which is below c
+    // - split a = k * d + r where k is below c and r is below d
+    //
+    // c * a = k * c * d + c * r = k * e + c * r (mod p)
+    //
+    // where c * r is at most c * d - c = p + e - c, which is below p, so that
+    // c * r - p + k * e is in [-p, c^2) and does not overflow
+    assert!(c > 0 && c <= 1 << 15);
+    let d = P.div_ceil(c);
+    let e = c * d - P;
+
+    script! {
+        OP_DUP
+        { d } OP_DIV
+        OP_TUCK
+        { d } OP_MUL OP_SUB
+        { c } OP_MUL
+        { P } OP_SUB
+        OP_SWAP
+        { e } OP_MUL
+        OP_ADD
+        if c * c > P {
+            { pf31_reduce::<P>() }
+        }
+        { pf31_adjust::<P>() }
+    }
+}
+
+// Input: x in [0, p)
+// Output: x * 2^16 in [0, p)
+fn pf31_shift16<const P: u32>() -> Script {
+    script! {
+        { pf31_mul_by_small_constant::<P>(1 << 8) }
+        { pf31_mul_by_small_constant::<P>(1 << 8) }
+    }
+}
+
+pub fn pf31_add<const P: u32>() -> Script {
+    if P == MOD {
+        return m31_add();
+    }
+
+    script! {
+        { P } OP_SUB
+        OP_ADD
+        { pf31_adjust::<P>() }
+    }
+}
+
+pub fn pf31_double<const P: u32>() -> Script {
+    if P == MOD {
+        return m31_double();
+    }
+
+    script! {
+        OP_DUP
+        { pf31_add::<P>() }
+    }
+}
+
+pub fn pf31_sub<const P: u32>() -> Script {
+    if P == MOD {
+        return m31_sub();
+    }
+
+    script! {
+        OP_SUB
+        { pf31_adjust::<P>() }
+    }
+}
+
+pub fn pf31_neg<const P: u32>() -> Script {
+    let () = PrimeField31::<P>::CHECK_MODULUS;
+
+    // zero is kept as zero, so that the output is canonical
+    script! {
+        OP_DUP
+        OP_IF
+            { P } OP_SWAP OP_SUB
+        OP_ENDIF
+    }
+}
+
+pub fn pf31_mul<const P: u32>() -> Script {
+    if P == MOD {
+        return m31_mul();
+    }
+
+    script! {
+        // idea:
+        // - split a into a_h and a_l where a = a_h * 2^16 + a_l, a_h is in [0, 2^15], and a_l is in [-2^15, 2^15)
+        // - split b into b_h and b_l in the same way
+        //
+        // a * b = ((a_h * b_h) * 2^16 + (a_h * b_l + a_l * b_h)) * 2^16 + a_l * b_l
+        //
+        // here, each of the four products is at most 2^30 in absolute value, so none of them overflows,
+        // but a_h * b_l + a_l * b_h may reach -2^31, so the two products are reduced before adding them.
+        //
+        // the remainders are taken with `OP_DIV`, and the two multiplications by 2^16 are done with
+        // `pf31_shift16`.
+
+        OP_SWAP
+        pf31_split
+        OP_ROT
+        pf31_split
+        // current stack: a_h a_l b_h b_l
+
+        // compute a_l * b_l
+        2 OP_PICK
+        OP_OVER
+        OP_MUL
+        { pf31_reduce::<P>() }
+        { pf31_adjust::<P>() }
+        OP_TOALTSTACK
+
+        // compute a_h * b_l + a_l * b_h
+        3 OP_PICK
+        OP_MUL
+        { pf31_reduce::<P>() }
+        { pf31_adjust::<P>() }
+        OP_ROT
+        2 OP_PICK
+        OP_MUL
+        { pf31_reduce::<P>() }
+        { pf31_adjust_negative::<P>() }
+        OP_ADD
+        { pf31_adjust::<P>() }
+        OP_TOALTSTACK
+
+        // compute a_h * b_h
+        OP_MUL
+        { pf31_reduce::<P>() }
+
+        // stack: a_h * b_h
+        // altstack: a_l * b_l   a_h * b_l + a_l * b_h
+
+        { pf31_shift16::<P>() }
+        OP_FROMALTSTACK
+        { pf31_add::<P>() }
+        { pf31_shift16::<P>() }
+        OP_FROMALTSTACK
+        { pf31_add::<P>() }
+    }
+}
+
+pub fn pf31_square<const P: u32>() -> Script {
+    if P == MOD {
+        return m31_square();
+    }
+
+    script! {
+        OP_DUP
+        { pf31_mul::<P>() }
+    }
+}
+
+pub fn pf31_pow_const<const P: u32>(e: u32) -> Script {
+    // left-to-right square-and-multiply over the bits of e
+    if e == 0 {
+        return script! {
+            OP_DROP
+            1
+        };
+    }
+
+    let bits = 32 - e.leading_zeros();
+    script! {
+        OP_DUP
+        for i in (0..bits - 1).rev() {
+            { pf31_square::<P>() }
+            if (e >> i) & 1 == 1 {
+                OP_OVER
+                { pf31_mul::<P>() }
+            }
+        }
+        OP_NIP
+    }
+}
+
+pub fn pf31_inverse<const P: u32>() -> Script {
+    if P == MOD {
+        return m31_inverse();
+    }
+
+    // note: zero is mapped to zero
+    pf31_pow_const::<P>(P - 2)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::exec::execute_script;
+    use crate::treepp::*;
+    use crate::{
+        pf31_add, pf31_double, pf31_inverse, pf31_mul, pf31_neg, pf31_pow_const, pf31_square,
+        pf31_sub, PrimeField31,
+    };
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+
+    fn test_prime_field<const P: u32>() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!("pf31 mul (p = {}): {}", P, pf31_mul::<P>().len());
+
+        let edge = [
+            0,
+            1,
+            P - 1,
+            P / 2,
+            (1 << 15).min(P - 1),
+            ((1 << 31) - (1 << 15)).min(P - 1),
+        ]
+        .map(PrimeField31::<P>::new);
+
+        let mut pairs = vec![];
+        for a in edge.iter() {
+            for b in edge.iter() {
+                pairs.push((*a, *b));
+            }
+        }
+        for _ in 0..50 {
+            pairs.push((prng.gen(), prng.gen()));
+        }
+
+        for (a, b) in pairs {
+            let script = script! {
+                { a }
+                { b }
+                { pf31_mul::<P>() }
+                { a * b }
+                OP_EQUALVERIFY
+                { a }
+                { b }
+                { pf31_add::<P>() }
+                { a + b }
+                OP_EQUALVERIFY
+                { a }
+                { b }
+                { pf31_sub::<P>() }
+                { a - b }
+                OP_EQUALVERIFY
+                { a }
+                { pf31_neg::<P>() }
+                { -a }
+                OP_EQUALVERIFY
+                { a }
+                { pf31_double::<P>() }
+                { a.double() }
+                OP_EQUALVERIFY
+                { a }
+                { pf31_square::<P>() }
+                { a.square() }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+
+        for a in edge.iter().take(3) {
+            let script = script! {
+                { *a }
+                { pf31_inverse::<P>() }
+                { a.inverse() }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+
+        let a: PrimeField31<P> = prng.gen();
+        for e in [0, 1, 2, 5, 1 << 20] {
+            let script = script! {
+                { a }
+                { pf31_pow_const::<P>(e) }
+                { a.pow(e as u128) }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_pf31_small() {
+        test_prime_field::<3>();
+        test_prime_field::<97>();
+        test_prime_field::<65537>();
+    }
+
+    #[test]
+    fn test_pf31_large() {
+        test_prime_field::<{ (1 << 30) - 35 }>();
+        test_prime_field::<{ 15 * (1 << 27) + 1 }>();
+        test_prime_field::<2147483629>();
+        test_prime_field::<{ (1 << 31) - 1 }>();
+    }
+}
@@ -1,9 +1,188 @@
-use crate::m31::{m31_add, m31_double, m31_sub};
+use crate::cm31::CM31;
+use crate::cm31::{
+    cm31_add, cm31_conjugate, cm31_copy, cm31_double, cm31_fromaltstack, cm31_inverse,
+    cm31_is_square, cm31_mul, cm31_mul_by_constant, cm31_neg, cm31_norm, cm31_roll, cm31_sqrt,
+    cm31_square, cm31_sub, cm31_toaltstack,
+};
+use crate::m31::M31;
+use crate::m31::{m31_add, m31_double, m31_is_square, m31_neg, m31_sub, MOD};
+use crate::m31_acc::{m31_acc_add, m31_acc_reduce, m31_mul_unreduced, M31Acc};
+use crate::treepp::pushable::{Builder, Pushable};
 use crate::treepp::*;
+use core::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+use p3_field::extension::{BinomialExtensionField, Complex};
+use p3_field::AbstractExtensionField;
+use p3_mersenne_31::Mersenne31 as P3M31;
+use rand::distributions::{Distribution, Standard};
 
+use crate::field::horner_eval;
 pub use crate::karatsuba_complex::*;
 use crate::m31_mul;
 
+/// An element x0 + x1 * u of the degree-4 extension QM31 = CM31[u] / (u^2 - 2 - i), stored as
+/// `QM31(x0, x1)`.
+///
+/// With x0 = a0 + b0 * i and x1 = a1 + b1 * i, it is pushed to the stack as `b1 a1 b0 a0`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct QM31(pub CM31, pub CM31);
+
+impl QM31 {
+    pub const ZERO: QM31 = QM31(CM31::ZERO, CM31::ZERO);
+    pub const ONE: QM31 = QM31(CM31::ONE, CM31::ZERO);
+
+    /// Builds a0 + b0 * i + (a1 + b1 * i) * u.
+    pub fn new(a0: u32, b0: u32, a1: u32, b1: u32) -> QM31 {
+        QM31(CM31::new(a0, b0), CM31::new(a1, b1))
+    }
+
+    /// The limbs in the stack order, i.e., `[b1, a1, b0, a0]`, as expected by
+    /// `qm31_mul_by_constant`.
+    pub fn limbs(&self) -> [u32; 4] {
+        let [b1, a1] = self.1.limbs();
+        let [b0, a0] = self.0.limbs();
+        [b1, a1, b0, a0]
+    }
+
+    /// The inverse of `limbs`.
+    pub fn from_limbs(limbs: [u32; 4]) -> QM31 {
+        QM31(
+            CM31::from_limbs([limbs[2], limbs[3]]),
+            CM31::from_limbs([limbs[0], limbs[1]]),
+        )
+    }
+
+    pub fn double(&self) -> QM31 {
+        *self + *self
+    }
+
+    pub fn square(&self) -> QM31 {
+        *self * *self
+    }
+
+    /// The conjugate x0 - x1 * u over CM31.
+    pub fn conjugate(&self) -> QM31 {
+        QM31(self.0, -self.1)
+    }
+
+    /// The norm x * conj(x) = x0^2 - (2 + i) * x1^2 down to CM31.
+    pub fn norm(&self) -> CM31 {
+        self.0.square() - NONRESIDUE * self.1.square()
+    }
+
+    pub fn pow(&self, mut e: u128) -> QM31 {
+        let mut res = QM31::ONE;
+        let mut base = *self;
+        while e > 0 {
+            if e & 1 == 1 {
+                res *= base;
+            }
+            base = base.square();
+            e >>= 1;
+        }
+        res
+    }
+
+    /// The inverse, where zero is mapped to zero as in `qm31_inverse`.
+    pub fn inverse(&self) -> QM31 {
+        let norm_inv = self.norm().inverse();
+        QM31(self.0 * norm_inv, -self.1 * norm_inv)
+    }
+}
+
+impl From<M31> for QM31 {
+    fn from(value: M31) -> QM31 {
+        QM31(value.into(), CM31::ZERO)
+    }
+}
+
+impl From<CM31> for QM31 {
+    fn from(value: CM31) -> QM31 {
+        QM31(value, CM31::ZERO)
+    }
+}
+
+impl From<BinomialExtensionField<Complex<P3M31>, 2>> for QM31 {
+    fn from(value: BinomialExtensionField<Complex<P3M31>, 2>) -> QM31 {
+        let limbs: &[Complex<P3M31>] = value.as_base_slice();
+        QM31(limbs[0].into(), limbs[1].into())
+    }
+}
+
+impl Add for QM31 {
+    type Output = QM31;
+
+    fn add(self, rhs: QM31) -> QM31 {
+        QM31(self.0 + rhs.0, self.1 + rhs.1)
+    }
+}
+
+impl Sub for QM31 {
+    type Output = QM31;
+
+    fn sub(self, rhs: QM31) -> QM31 {
+        QM31(self.0 - rhs.0, self.1 - rhs.1)
+    }
+}
+
+impl Neg for QM31 {
+    type Output = QM31;
+
+    fn neg(self) -> QM31 {
+        QM31(-self.0, -self.1)
+    }
+}
+
+impl Mul for QM31 {
+    type Output = QM31;
+
+    fn mul(self, rhs: QM31) -> QM31 {
+        // (x0 + x1 * u) * (y0 + y1 * u) = (x0 * y0 + (2 + i) * x1 * y1) + (x0 * y1 + x1 * y0) * u
+        QM31(
+            self.0 * rhs.0 + NONRESIDUE * self.1 * rhs.1,
+            self.0 * rhs.1 + self.1 * rhs.0,
+        )
+    }
+}
+
+impl Div for QM31 {
+    type Output = QM31;
+
+    fn div(self, rhs: QM31) -> QM31 {
+        self * rhs.inverse()
+    }
+}
+
+impl AddAssign for QM31 {
+    fn add_assign(&mut self, rhs: QM31) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for QM31 {
+    fn sub_assign(&mut self, rhs: QM31) {
+        *self = *self - rhs;
+    }
+}
+
+impl MulAssign for QM31 {
+    fn mul_assign(&mut self, rhs: QM31) {
+        *self = *self * rhs;
+    }
+}
+
+impl Distribution<QM31> for Standard {
+    fn sample<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> QM31 {
+        QM31(rng.gen(), rng.gen())
+    }
+}
+
+impl Pushable for QM31 {
+    fn bitcoin_script_push(self, builder: Builder) -> Builder {
+        let builder = self.1.bitcoin_script_push(builder);
+        self.0.bitcoin_script_push(builder)
+    }
+}
+
 pub fn qm31_add() -> Script {
     script! {
         for i in 0..3 {
@@ -42,6 +221,15 @@ pub fn qm31_sub() -> Script {
     }
 }
 
+pub fn qm31_neg() -> Script {
+    script! {
+        cm31_neg
+        OP_2SWAP
+        cm31_neg
+        OP_2SWAP
+    }
+}
+
 pub fn qm31_double() -> Script {
     script! {
         for _ in 0..3 {
@@ -108,6 +296,115 @@ pub fn qm31_mul_m31() -> Script {
     }
 }
 
+pub fn qm31_mul_cm31() -> Script {
+    // input stack:
+    //
+    // qm31
+    // b1 a1 b0 a0
+    //
+    // cm31
+    // d c
+    script! {
+        { cm31_copy(0) }
+
+        // (a0 + b0 * i) * (c + d * i)
+        { cm31_roll(2) } cm31_mul
+        cm31_toaltstack
+
+        // (a1 + b1 * i) * (c + d * i)
+        cm31_mul
+        cm31_fromaltstack
+    }
+}
+
+// the non-residue 2 + i
+const NONRESIDUE: CM31 = CM31(M31::new(2), M31::new(1));
+
+pub fn qm31_mul_by_constant(c: [u32; 4]) -> Script {
+    // input stack: b1 a1 b0 a0
+    // c is given in the stack order, i.e., c = [d1, c1, d0, c0] for
+    // (c0 + d0 * i) + (c1 + d1 * i) * u
+    //
+    // (x0 + x1 * u) * (y0 + y1 * u) = (x0 * y0 + (2 + i) * y1 * x1) + (x0 * y1 + x1 * y0) * u
+    let c = c.map(|v| v % MOD);
+    let y1 = [c[0], c[1]];
+    let y0 = [c[2], c[3]];
+
+    // (2 + i) * y1, computed at compile time
+    let y1_nr = (NONRESIDUE * CM31::from_limbs(y1)).limbs();
+
+    if c == [0, 0, 0, 1] {
+        return script! {};
+    }
+
+    if c == [0, 0, 0, 0] {
+        return script! {
+            OP_2DROP OP_2DROP
+            0 0 0 0
+        };
+    }
+
+    if y1 == [0, 0] {
+        return script! {
+            { cm31_mul_by_constant(y0) }
+            cm31_toaltstack
+            { cm31_mul_by_constant(y0) }
+            cm31_fromaltstack
+        };
+    }
+
+    if y0 == [0, 0] {
+        return script! {
+            { cm31_mul_by_constant(y1) }
+            { cm31_roll(1) }
+            { cm31_mul_by_constant(y1_nr) }
+        };
+    }
+
+    let schoolbook = script! {
+        // x0 * y0 + x1 * (2 + i) * y1
+        { cm31_copy(1) } { cm31_copy(1) }
+        { cm31_mul_by_constant(y0) }
+        { cm31_roll(1) }
+        { cm31_mul_by_constant(y1_nr) }
+        cm31_add
+        cm31_toaltstack
+
+        // x0 * y1 + x1 * y0
+        { cm31_mul_by_constant(y1) }
+        { cm31_roll(1) }
+        { cm31_mul_by_constant(y0) }
+        cm31_add
+        cm31_fromaltstack
+    };
+
+    // Karatsuba: x0 * y1 + x1 * y0 = (x0 + x1) * (y0 + y1) - x0 * y0 - x1 * y1
+    let y_sum = [(y0[0] + y1[0]) % MOD, (y0[1] + y1[1]) % MOD];
+    let karatsuba = script! {
+        { cm31_copy(1) } { cm31_copy(1) } cm31_add
+        { cm31_mul_by_constant(y_sum) }
+        cm31_toaltstack
+
+        { cm31_mul_by_constant(y0) }
+        { cm31_roll(1) }
+        { cm31_mul_by_constant(y1) }
+        // current stack: x0 * y0, x1 * y1
+
+        { cm31_copy(1) } { cm31_copy(1) } cm31_add
+        cm31_fromaltstack { cm31_roll(1) } cm31_sub
+        cm31_toaltstack
+
+        cm31_mul_by_nonresidue cm31_add
+        cm31_fromaltstack { cm31_roll(1) }
+    };
+
+    if karatsuba.len() < schoolbook.len() {
+        karatsuba
+    } else {
+        schoolbook
+    }
+}
+
 pub fn qm31_toaltstack() -> Script {
     script! {
         for _ in 0..4 {
@@ -144,16 +441,581 @@ pub fn qm31_roll(offset: usize) -> Script {
     }
 }
 
+// multiply a cm31 element by the non-residue 2 + i
+//
+// (2 + i) * (a + b * i) = (2 * a - b) + (a + 2 * b) * i
+fn cm31_mul_by_nonresidue() -> Script {
+    script! {
+        OP_DUP m31_double 2 OP_PICK m31_sub
+        OP_ROT OP_ROT
+        OP_SWAP m31_double m31_add
+        OP_SWAP
+    }
+}
+
+pub fn qm31_square() -> Script {
+    // input stack: b1 a1 b0 a0
+    // which is x = x0 + x1 * u with x0 = a0 + b0 * i and x1 = a1 + b1 * i
+    //
+    // x^2 = (x0^2 + (2 + i) * x1^2) + 2 * x0 * x1 * u
+    script! {
+        // compute 2 * x0 * x1
+        { cm31_copy(1) } { cm31_copy(1) } cm31_mul cm31_double
+
+        // compute (2 + i) * x1^2
+        { cm31_roll(2) } cm31_square cm31_mul_by_nonresidue
+
+        // compute x0^2 + (2 + i) * x1^2
+        { cm31_roll(2) } cm31_square cm31_add
+    }
+}
+
+pub fn qm31_conjugate() -> Script {
+    // input stack: b1 a1 b0 a0
+    // output stack: -b1 -a1 b0 a0
+    //
+    // (x0 + x1 * u) -> (x0 - x1 * u), i.e., x -> x^(p^2)
+    script! {
+        OP_2SWAP
+        cm31_neg
+        OP_2SWAP
+    }
+}
+
+pub fn qm31_frobenius(k: usize) -> Script {
+    // input stack: x
+    // output stack: x^(p^k)
+    //
+    // for x = x0 + x1 * u, since i^p = -i and u^(p^k) = (2 + i)^((p^k - 1) / 2) * u,
+    //
+    // x^(p^k) = conj^k(x0) + conj^k(x1) * (2 + i)^((p^k - 1) / 2) * u
+    //
+    // the automorphism group is cyclic of order 4, and for even k the constant is 1 or -1
+    match k % 4 {
+        0 => script! {},
+        2 => qm31_conjugate(),
+        k => {
+            let e = ((MOD as u128).pow(k as u32) - 1) / 2;
+            let w = NONRESIDUE.pow(e).limbs();
+
+            script! {
+                cm31_conjugate
+                OP_2SWAP
+                cm31_conjugate
+                { cm31_mul_by_constant(w) }
+                OP_2SWAP
+            }
+        }
+    }
+}
+
+pub fn qm31_norm_to_cm31() -> Script {
+    // input stack: b1 a1 b0 a0
+    // output stack: the cm31 element x * conj(x) = x0^2 - (2 + i) * x1^2
+    script! {
+        cm31_square
+        OP_2SWAP
+        cm31_square
+        cm31_mul_by_nonresidue
+        cm31_sub
+    }
+}
+
+pub fn qm31_norm_to_m31() -> Script {
+    // input stack: b1 a1 b0 a0
+    // output stack: the m31 element x * x^p * x^(p^2) * x^(p^3)
+    script! {
+        qm31_norm_to_cm31
+        cm31_norm
+    }
+}
+
+pub fn qm31_is_square() -> Script {
+    // input stack: b1 a1 b0 a0
+    // output stack: 1 if x is a square (including 0), 0 otherwise
+    //
+    // x is a square if and only if its norm to m31 is a square
+    script! {
+        qm31_norm_to_m31
+        m31_is_square
+    }
+}
+
+pub fn qm31_sqrt() -> Script {
+    // input stack: b1 a1 b0 a0
+    // which is z = a + b * u with a = a0 + b0 * i and b = a1 + b1 * i
+    //
+    // output stack: x such that x^2 = z
+    //
+    // idea, with x = x0 + x1 * u and v = 2 + i:
+    // - x^2 = (x0^2 + v * x1^2) + 2 * x0 * x1 * u
+    // - n = sqrt(a^2 - v * b^2) is computed with cm31_sqrt, which fails if z is not a square
+    // - x0^2 = t1 = (a + n) / 2 and x1^2 = t2 = (a - n) / (2 * v), with t1 * t2 = b^2 / 4
+    // - if t1 or t2 is not a square, n is replaced by -n, which turns (t1, t2) into
+    //   (v * t2, t1 / v), and both of them are squares since v is not a square
+    // - the sign of x1 is fixed so that 2 * x0 * x1 = b
+    let nonresidue_inv = NONRESIDUE.inverse();
+    let half = CM31::from(M31::new(2).inverse());
+
+    script! {
+        { qm31_copy(0) }
+        qm31_norm_to_cm31
+        cm31_sqrt
+        // current stack: b a n
+
+        { cm31_copy(1) } { cm31_copy(1) }
+        cm31_sub
+        { cm31_mul_by_constant((half * nonresidue_inv).limbs()) }
+        { cm31_roll(2) } { cm31_roll(2) }
+        cm31_add
+        { cm31_mul_by_constant(half.limbs()) }
+        // current stack: b t2 t1
+
+        { cm31_copy(0) } cm31_is_square
+        4 OP_PICK 4 OP_PICK cm31_is_square
+        OP_BOOLAND
+        OP_NOTIF
+            { cm31_mul_by_constant(nonresidue_inv.limbs()) }
+            OP_2SWAP
+            cm31_mul_by_nonresidue
+        OP_ENDIF
+
+        cm31_sqrt
+        OP_2SWAP
+        cm31_sqrt
+        // current stack: b x0 x1
+
+        { cm31_copy(1) } { cm31_copy(1) }
+        cm31_mul cm31_double
+        { cm31_roll(3) }
+        OP_ROT OP_EQUAL OP_TOALTSTACK
+        OP_EQUAL OP_FROMALTSTACK OP_BOOLAND
+        OP_NOTIF
+            cm31_neg
+        OP_ENDIF
+        OP_2SWAP
+    }
+}
+
+pub fn qm31_sqrt_with_hint() -> Script {
+    // input stack: z r
+    // where r is provided by the prover as a hint
+    //
+    // the script checks that r is a valid qm31 element and that r^2 = z,
+    // both r and -r are accepted
+    //
+    // output stack: r
+    script! {
+        for i in 0..4 {
+            { i } OP_PICK
+            0 { MOD } OP_WITHIN OP_VERIFY
+        }
+        { qm31_copy(0) }
+        qm31_square
+        { qm31_roll(2) }
+        qm31_equalverify
+    }
+}
+
+pub fn qm31_inverse() -> Script {
+    // input stack: b1 a1 b0 a0
+    // which is x = x0 + x1 * u with x0 = a0 + b0 * i and x1 = a1 + b1 * i
+    //
+    // idea:
+    // - the conjugate of x over cm31 is x0 - x1 * u
+    // - x * (x0 - x1 * u) = x0^2 - (2 + i) * x1^2 = N, which lies in cm31
+    // - the inverse of N = Nr + Ni * i is (Nr - Ni * i) / (Nr^2 + Ni^2)
+    // - x^-1 = (x0 - x1 * u) * N^-1
+    //
+    // note: zero is mapped to zero
+    script! {
+        // compute x0^2 and x1^2
+        OP_2DUP cm31_square
+        5 OP_PICK 5 OP_PICK cm31_square
+        // current stack: b1 a1 b0 a0 s_i s_r t_i t_r
+
+        // compute (2 + i) * x1^2
+        cm31_mul_by_nonresidue
+
+        // compute N = x0^2 - (2 + i) * x1^2
+        cm31_sub
+
+        // compute x0 * N^-1 and -x1 * N^-1
+        cm31_inverse
+        OP_2DUP { cm31_roll(2) } cm31_mul
+        cm31_toaltstack
+        cm31_mul cm31_neg
+        cm31_fromaltstack
+    }
+}
+
+pub fn qm31_inverse_with_hint() -> Script {
+    // input stack: x y
+    // where y is provided by the prover as a hint
+    //
+    // the script checks that y is a valid qm31 element and that x * y = 1,
+    // which also rejects x = 0
+    script! {
+        for i in 0..4 {
+            { i } OP_PICK
+            0 { MOD } OP_WITHIN OP_VERIFY
+        }
+        { qm31_copy(0) }
+        { qm31_roll(2) }
+        qm31_mul
+        1 OP_EQUALVERIFY
+        0 OP_EQUALVERIFY
+        0 OP_EQUALVERIFY
+        0 OP_EQUALVERIFY
+    }
+}
+
+pub fn qm31_div() -> Script {
+    // input stack: x y
+    // output stack: x / y
+    script! {
+        qm31_inverse
+        qm31_mul
+    }
+}
+
+pub fn qm31_div_with_hint() -> Script {
+    // input stack: x y z
+    // where z = x / y is provided by the prover as a hint
+    //
+    // the script checks that z is a valid qm31 element, that y is nonzero, and that y * z = x
+    script! {
+        for i in 0..4 {
+            { i } OP_PICK
+            0 { MOD } OP_WITHIN OP_VERIFY
+        }
+        for _ in 0..4 {
+            7 OP_PICK
+        }
+        OP_BOOLOR OP_BOOLOR OP_BOOLOR OP_VERIFY
+        { qm31_copy(0) }
+        { qm31_roll(2) }
+        qm31_mul
+        { qm31_roll(2) }
+        qm31_equalverify
+    }
+}
+
+pub fn qm31_batch_inverse(n: usize) -> Script {
+    // input stack: x_0 x_1 ... x_{n - 1}
+    // output stack: x_0^-1 x_1^-1 ... x_{n - 1}^-1
+    //
+    // Montgomery's trick: with the prefix products p_i = x_0 * ... * x_i, a single inverse
+    // of p_{n - 1} gives all the inverses through
+    //
+    // x_i^-1 = p_{n - 1}^-1 * (x_{i + 1} * ... * x_{n - 1}) * p_{i - 1}
+    //
+    // note: all the elements must be nonzero, otherwise every output is zero
+    if n == 0 {
+        return script! {};
+    }
+
+    script! {
+        for _ in 1..n {
+            qm31_toaltstack
+        }
+
+        // compute the prefix products
+        // current stack after each step: p_0 x_1 p_1 ... x_i p_i
+        for _ in 1..n {
+            qm31_fromaltstack
+            { qm31_copy(0) }
+            { qm31_copy(2) }
+            qm31_mul
+        }
+
+        qm31_inverse
+
+        // current stack: p_0 x_1 p_1 ... p_{i - 1} x_i (x_0 * ... * x_i)^-1
+        for _ in 1..n {
+            { qm31_copy(0) }
+            { qm31_roll(3) }
+            qm31_mul
+            qm31_toaltstack
+            qm31_mul
+        }
+
+        for _ in 1..n {
+            qm31_fromaltstack
+        }
+    }
+}
+
+pub fn qm31_batch_inverse_with_hint(n: usize) -> Script {
+    // input stack: x_0 ... x_{n - 1} y_0 ... y_{n - 1} r
+    // where y_0, ..., y_{n - 1} are provided by the prover as hints, and r is a random
+    // challenge that the prover cannot predict when choosing the hints, see
+    // m31_batch_inverse_with_hint
+    //
+    // output stack: y_0 ... y_{n - 1}
+    //
+    // the script checks that every y_i is a valid qm31 element and, with a single randomised
+    // product check, that
+    //
+    // (r - x_0 * y_0) * ... * (r - x_{n - 1} * y_{n - 1}) = (r - 1)^n
+    //
+    // so a wrong hint passes with probability at most n / p^4
+    script! {
+        0 0 0 1
+        // current stack after each step: x_0 ... x_i y_0 ... y_i r acc
+        for i in (0..n).rev() {
+            for j in 0..4 {
+                { 8 + j } OP_PICK
+                0 { MOD } OP_WITHIN OP_VERIFY
+            }
+
+            { qm31_roll(i + 3) }
+            { qm31_copy(3) }
+            qm31_mul
+            { qm31_copy(2) }
+            { qm31_roll(1) }
+            qm31_sub
+            qm31_mul
+
+            { qm31_roll(2) }
+            qm31_toaltstack
+        }
+
+        { qm31_roll(1) }
+        0 0 0 1
+        qm31_sub
+        { qm31_pow_small(n) }
+        qm31_equalverify
+
+        for _ in 0..n {
+            qm31_fromaltstack
+        }
+    }
+}
+
+// Input: x
+// Output: x^e, by square-and-multiply
+fn qm31_pow_small(e: usize) -> Script {
+    if e == 0 {
+        return script! {
+            OP_2DROP OP_2DROP
+            0 0 0 1
+        };
+    }
+
+    let bits = usize::BITS - e.leading_zeros();
+    script! {
+        { qm31_copy(0) }
+        for i in (0..bits - 1).rev() {
+            qm31_square
+            if (e >> i) & 1 == 1 {
+                { qm31_copy(1) }
+                qm31_mul
+            }
+        }
+        { qm31_roll(1) }
+        OP_2DROP OP_2DROP
+    }
+}
+
+// Input:
+//      [G] (optional, four unreduced accumulators with bounds `acc`)
+//      b1 a1 b0 a0
+//      d1 c1 d0 c0
+// Output:
+//      G + (b1 a1 b0 a0) * (d1 c1 d0 c0) as four unreduced accumulators
+//
+// with x = (a0 + b0 * i) + (a1 + b1 * i) * u, y = (c0 + d0 * i) + (c1 + d1 * i) * u and u^2 = 2 + i,
+//
+// x * y = (x0 * y0 + x1 * w) + (x0 * y1 + x1 * y0) * u where w = (2 + i) * y1
+//
+// so every one of the 16 m31 products contributes to exactly one coordinate.
+fn qm31_mul_acc_unreduced(acc: Option<M31Acc>) -> (Script, M31Acc) {
+    // base depths of the operands after the preparation:
+    //      b1 a1 b0 a0 d1 c1 d0 c0 wr wi -d0 -d1 -wi
+    let terms = [
+        // real part of the first coordinate: a0 * c0 - b0 * d0 + a1 * wr - b1 * wi
+        [(9, 5), (10, 2), (11, 4), (12, 0)],
+        // imaginary part of the first coordinate: a0 * d0 + b0 * c0 + a1 * wi + b1 * wr
+        [(9, 6), (10, 5), (11, 3), (12, 4)],
+        // real part of the second coordinate: a0 * c1 - b0 * d1 + a1 * c0 - b1 * d0
+        [(9, 7), (10, 1), (11, 5), (12, 2)],
+        // imaginary part of the second coordinate: a0 * d1 + b0 * c1 + a1 * d0 + b1 * c0
+        [(9, 8), (10, 7), (11, 6), (12, 5)],
+    ];
+
+    let mut local = M31Acc::MUL;
+    let mut local_add_scripts = vec![];
+    for _ in 1..4 {
+        let (add_script, new_local) = m31_acc_add(local, M31Acc::MUL);
+        local_add_scripts.push(add_script);
+        local = new_local;
+    }
+
+    // add the local accumulators to the global ones, keeping the coordinate order
+    let (combine_script, global) = match acc {
+        Some(acc) => {
+            let (add_script, new_acc) = m31_acc_add(acc, local);
+            let combine_script = script! {
+                for _ in 0..4 {
+                    11 OP_ROLL 11 OP_ROLL 11 OP_ROLL
+                    OP_FROMALTSTACK OP_FROMALTSTACK OP_FROMALTSTACK
+                    { add_script.clone() }
+                }
+            };
+            (combine_script, new_acc)
+        }
+        None => {
+            let combine_script = script! {
+                for _ in 0..12 {
+                    OP_FROMALTSTACK
+                }
+            };
+            (combine_script, local)
+        }
+    };
+
+    let script = script! {
+        // compute w = (2 * c1 - d1) + (c1 + 2 * d1) * i
+        2 OP_PICK m31_double 4 OP_PICK m31_sub
+        4 OP_PICK m31_double 4 OP_PICK m31_add
+
+        // compute -d0, -d1, -wi
+        3 OP_PICK m31_neg
+        6 OP_PICK m31_neg
+        2 OP_PICK m31_neg
+
+        for term in terms.iter() {
+            { term[0].0 } OP_PICK { term[0].1 + 1 } OP_PICK
+            m31_mul_unreduced
+            for (operands, add_script) in term[1..].iter().zip(local_add_scripts.iter()) {
+                { operands.0 + 3 } OP_PICK { operands.1 + 4 } OP_PICK
+                m31_mul_unreduced
+                { add_script.clone() }
+            }
+            OP_TOALTSTACK OP_TOALTSTACK OP_TOALTSTACK
+        }
+
+        for _ in 0..6 {
+            OP_2DROP
+        }
+        OP_DROP
+
+        { combine_script }
+    };
+
+    (script, global)
+}
+
+// Input: x_0 ... x_{n-1} y_0 ... y_{n-1}
+// Output: x_0 * y_0 + ... + x_{n-1} * y_{n-1}
+pub fn qm31_inner_product(n: usize) -> Script {
+    if n == 0 {
+        return script! {
+            0 0 0 0
+        };
+    }
+
+    let mut acc = None;
+    let mut step_scripts = vec![];
+    for _ in 0..n {
+        let (step_script, new_acc) = qm31_mul_acc_unreduced(acc);
+        step_scripts.push(step_script);
+        acc = Some(new_acc);
+    }
+    let acc = acc.unwrap();
+
+    script! {
+        { qm31_roll(n) }
+        { qm31_roll(1) }
+        { step_scripts[0].clone() }
+        for i in (0..n - 1).rev() {
+            { qm31_roll(i + 4) }
+            { qm31_roll(4) }
+            { step_scripts[n - 1 - i].clone() }
+        }
+        for _ in 0..4 {
+            { m31_acc_reduce(acc) }
+            OP_TOALTSTACK
+        }
+        qm31_fromaltstack
+    }
+}
+
+pub fn qm31_horner_eval(degree: usize) -> Script {
+    horner_eval::<QM31>(degree)
+}
+
+pub fn qm31_horner_eval_const(coeffs: &[QM31]) -> Script {
+    // input stack: z
+    // where the coefficients c_0, ..., c_d are constants, starting with the constant term
+    //
+    // output stack: c_0 + c_1 * z + ... + c_d * z^d
+    let add_constant = |c: QM31| {
+        if c == QM31::ZERO {
+            script! {}
+        } else {
+            script! {
+                { c }
+                qm31_add
+            }
+        }
+    };
+
+    let degree = match coeffs.len() {
+        0 => {
+            return script! {
+                OP_2DROP OP_2DROP
+                0 0 0 0
+            }
+        }
+        len => len - 1,
+    };
+
+    if degree == 0 {
+        return script! {
+            OP_2DROP OP_2DROP
+            { coeffs[0] }
+        };
+    }
+
+    script! {
+        // the leading coefficient is multiplied with z directly
+        if degree > 1 {
+            { qm31_copy(0) }
+        }
+        { qm31_mul_by_constant(coeffs[degree].limbs()) }
+        { add_constant(coeffs[degree - 1]) }
+
+        // current stack after each step: z acc
+        for k in (0..degree - 1).rev() {
+            if k > 0 {
+                { qm31_copy(1) }
+            } else {
+                { qm31_roll(1) }
+            }
+            qm31_mul
+            { add_constant(coeffs[k]) }
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use crate::exec::execute_script;
+    use crate::m31::MOD;
     use crate::treepp::*;
     use crate::{
-        qm31_add, qm31_copy, qm31_double, qm31_equalverify, qm31_mul, qm31_mul_m31, qm31_roll,
-        qm31_sub,
+        cm31_equalverify, qm31_add, qm31_batch_inverse, qm31_batch_inverse_with_hint,
+        qm31_conjugate, qm31_copy, qm31_div, qm31_div_with_hint, qm31_double, qm31_equalverify,
+        qm31_frobenius, qm31_horner_eval, qm31_horner_eval_const, qm31_inner_product, qm31_inverse,
+        qm31_inverse_with_hint, qm31_is_square, qm31_mul, qm31_mul_by_constant, qm31_mul_cm31,
+        qm31_mul_m31, qm31_neg, qm31_norm_to_cm31, qm31_norm_to_m31, qm31_roll, qm31_sqrt,
+        qm31_sqrt_with_hint, qm31_square, qm31_sub, CM31, M31, QM31,
     };
-    use core::ops::{Add, Mul, Neg};
+    use core::ops::{Add, Mul, Neg, Sub};
     use p3_field::extension::Complex;
-    use p3_field::{AbstractExtensionField, AbstractField, PrimeField32};
+    use p3_field::{AbstractExtensionField, AbstractField, Field, PrimeField32};
     use rand::{Rng, SeedableRng};
     use rand_chacha::ChaCha20Rng;
 
@@ -166,27 +1028,13 @@ mod test {
 
         let a = rng.gen::<F>();
         let b = rng.gen::<F>();
-
         let c = a.add(b);
 
-        let a: &[Complex<p3_mersenne_31::Mersenne31>] = a.as_base_slice();
-        let b: &[Complex<p3_mersenne_31::Mersenne31>] = b.as_base_slice();
-        let c: &[Complex<p3_mersenne_31::Mersenne31>] = c.as_base_slice();
-
         let script = script! {
-            { a[1].imag().as_canonical_u32() }
-            { a[1].real().as_canonical_u32() }
-            { a[0].imag().as_canonical_u32() }
-            { a[0].real().as_canonical_u32() }
-            { b[1].imag().as_canonical_u32() }
-            { b[1].real().as_canonical_u32() }
-            { b[0].imag().as_canonical_u32() }
-            { b[0].real().as_canonical_u32() }
+            { QM31::from(a) }
+            { QM31::from(b) }
             qm31_add
-            { c[1].imag().as_canonical_u32() }
-            { c[1].real().as_canonical_u32() }
-            { c[0].imag().as_canonical_u32() }
-            { c[0].real().as_canonical_u32() }
+            { QM31::from(c) }
             qm31_equalverify
             OP_PUSHNUM_1
         };
@@ -201,19 +1049,28 @@ mod test {
         let a = rng.gen::<F>();
         let c = a.double();
 
-        let a: &[Complex<p3_mersenne_31::Mersenne31>] = a.as_base_slice();
-        let c: &[Complex<p3_mersenne_31::Mersenne31>] = c.as_base_slice();
-
         let script = script! {
-            { a[1].imag().as_canonical_u32() }
-            { a[1].real().as_canonical_u32() }
-            { a[0].imag().as_canonical_u32() }
-            { a[0].real().as_canonical_u32() }
+            { QM31::from(a) }
             qm31_double
-            { c[1].imag().as_canonical_u32() }
-            { c[1].real().as_canonical_u32() }
-            { c[0].imag().as_canonical_u32() }
-            { c[0].real().as_canonical_u32() }
+            { QM31::from(c) }
+            qm31_equalverify
+            OP_PUSHNUM_1
+        };
+        let exec_result = execute_script(script);
+        assert!(exec_result.success);
+    }
+
+    #[test]
+    fn test_qm31_neg() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+
+        let a = rng.gen::<F>();
+        let c = -a;
+
+        let script = script! {
+            { QM31::from(a) }
+            qm31_neg
+            { QM31::from(c) }
             qm31_equalverify
             OP_PUSHNUM_1
         };
@@ -230,24 +1087,11 @@ mod test {
         let b = rng.gen::<F>();
         let c = a.add(b.neg());
 
-        let a: &[Complex<p3_mersenne_31::Mersenne31>] = a.as_base_slice();
-        let b: &[Complex<p3_mersenne_31::Mersenne31>] = b.as_base_slice();
-        let c: &[Complex<p3_mersenne_31::Mersenne31>] = c.as_base_slice();
-
         let script = script! {
-            { a[1].imag().as_canonical_u32() }
-            { a[1].real().as_canonical_u32() }
-            { a[0].imag().as_canonical_u32() }
-            { a[0].real().as_canonical_u32() }
-            { b[1].imag().as_canonical_u32() }
-            { b[1].real().as_canonical_u32() }
-            { b[0].imag().as_canonical_u32() }
-            { b[0].real().as_canonical_u32() }
+            { QM31::from(a) }
+            { QM31::from(b) }
             qm31_sub
-            { c[1].imag().as_canonical_u32() }
-            { c[1].real().as_canonical_u32() }
-            { c[0].imag().as_canonical_u32() }
-            { c[0].real().as_canonical_u32() }
+            { QM31::from(c) }
             qm31_equalverify
             OP_PUSHNUM_1
         };
@@ -264,24 +1108,11 @@ mod test {
         let b = rng.gen::<F>();
         let c = a.mul(b);
 
-        let a: &[Complex<p3_mersenne_31::Mersenne31>] = a.as_base_slice();
-        let b: &[Complex<p3_mersenne_31::Mersenne31>] = b.as_base_slice();
-        let c: &[Complex<p3_mersenne_31::Mersenne31>] = c.as_base_slice();
-
         let script = script! {
-            { a[1].imag().as_canonical_u32() }
-            { a[1].real().as_canonical_u32() }
-            { a[0].imag().as_canonical_u32() }
-            { a[0].real().as_canonical_u32() }
-            { b[1].imag().as_canonical_u32() }
-            { b[1].real().as_canonical_u32() }
-            { b[0].imag().as_canonical_u32() }
-            { b[0].real().as_canonical_u32() }
+            { QM31::from(a) }
+            { QM31::from(b) }
             qm31_mul
-            { c[1].imag().as_canonical_u32() }
-            { c[1].real().as_canonical_u32() }
-            { c[0].imag().as_canonical_u32() }
-            { c[0].real().as_canonical_u32() }
+            { QM31::from(c) }
             qm31_equalverify
             OP_PUSHNUM_1
         };
@@ -304,20 +1135,11 @@ mod test {
             Complex::<p3_mersenne_31::Mersenne31>::zero(),
         );
 
-        let a: &[Complex<p3_mersenne_31::Mersenne31>] = a.as_base_slice();
-        let c: &[Complex<p3_mersenne_31::Mersenne31>] = c.as_base_slice();
-
         let script = script! {
-            { a[1].imag().as_canonical_u32() }
-            { a[1].real().as_canonical_u32() }
-            { a[0].imag().as_canonical_u32() }
-            { a[0].real().as_canonical_u32() }
-            { b.as_canonical_u32() }
+            { QM31::from(a) }
+            { M31::from(b) }
             { mul_script.clone() }
-            { c[1].imag().as_canonical_u32() }
-            { c[1].real().as_canonical_u32() }
-            { c[0].imag().as_canonical_u32() }
-            { c[0].real().as_canonical_u32() }
+            { QM31::from(c) }
             qm31_equalverify
             OP_TRUE
         };
@@ -327,41 +1149,363 @@ mod test {
     }
 
     #[test]
-    fn test_qm31_copy() {
+    fn test_qm31_mul_cm31() {
+        let mul_script = qm31_mul_cm31();
+
         let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!("qm31 mul_by_cm31: {}", mul_script.len());
 
-        let a = rng.gen::<F>();
-        let b = rng.gen::<F>();
+        for _ in 0..20 {
+            let a = rng.gen::<F>();
+            let b = rng.gen::<Complex<p3_mersenne_31::Mersenne31>>();
+
+            let c = a * F::new(b, Complex::<p3_mersenne_31::Mersenne31>::zero());
+
+            let script = script! {
+                { QM31::from(a) }
+                { CM31::from(b) }
+                { mul_script.clone() }
+                { QM31::from(c) }
+                qm31_equalverify
+                OP_TRUE
+            };
 
-        let a: &[Complex<p3_mersenne_31::Mersenne31>] = a.as_base_slice();
-        let b: &[Complex<p3_mersenne_31::Mersenne31>] = b.as_base_slice();
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_qm31_mul_by_constant() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+
+        let nonresidue = F::new(
+            Complex::<p3_mersenne_31::Mersenne31>::new(
+                p3_mersenne_31::Mersenne31::two(),
+                p3_mersenne_31::Mersenne31::one(),
+            ),
+            Complex::<p3_mersenne_31::Mersenne31>::zero(),
+        );
+
+        let mut constants = vec![F::zero(), F::one(), F::one().neg(), nonresidue];
+        for _ in 0..10 {
+            constants.push(rng.gen::<F>());
+        }
+
+        for c in constants {
+            let mul_script = qm31_mul_by_constant(QM31::from(c).limbs());
+            eprintln!("qm31 mul_by_constant: {}", mul_script.len());
+
+            let a = rng.gen::<F>();
+            let b = a * c;
+
+            let script = script! {
+                { QM31::from(a) }
+                { mul_script.clone() }
+                { QM31::from(b) }
+                qm31_equalverify
+                OP_TRUE
+            };
+
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_qm31_frobenius_norm() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!("qm31 conjugate: {}", qm31_conjugate().len());
+        eprintln!("qm31 frobenius: {}", qm31_frobenius(1).len());
+        eprintln!("qm31 norm_to_cm31: {}", qm31_norm_to_cm31().len());
+        eprintln!("qm31 norm_to_m31: {}", qm31_norm_to_m31().len());
+
+        let order = p3_mersenne_31::Mersenne31::ORDER_U32 as u64;
+
+        for _ in 0..5 {
+            let a = rng.gen::<F>();
+
+            let mut conjugates = vec![a];
+            for _ in 0..4 {
+                conjugates.push(conjugates.last().unwrap().exp_u64(order));
+            }
+
+            // both norms lie in the subfield, i.e., in the first cm31 component
+            let norm_cm31 = QM31::from(a * conjugates[2]).0;
+            let norm_m31 = QM31::from(a * conjugates[2] * conjugates[1] * conjugates[3])
+                .0
+                .real();
+
+            let script = script! {
+                for (k, conjugate) in conjugates.iter().enumerate() {
+                    { QM31::from(a) }
+                    { qm31_frobenius(k) }
+                    { QM31::from(*conjugate) }
+                    qm31_equalverify
+                }
+                { QM31::from(a) }
+                qm31_conjugate
+                { QM31::from(conjugates[2]) }
+                qm31_equalverify
+                { QM31::from(a) }
+                qm31_norm_to_cm31
+                { norm_cm31 }
+                cm31_equalverify
+                { QM31::from(a) }
+                qm31_norm_to_m31
+                { norm_m31 }
+                OP_EQUAL
+            };
+
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_qm31_sqrt() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!("qm31 is_square: {}", qm31_is_square().len());
+        eprintln!("qm31 sqrt: {}", qm31_sqrt().len());
+        eprintln!("qm31 sqrt with hint: {}", qm31_sqrt_with_hint().len());
+
+        let order = p3_mersenne_31::Mersenne31::ORDER_U32 as u64;
+
+        for _ in 0..3 {
+            let a = rng.gen::<F>();
+            let b = a.square();
+
+            // a is a square if and only if its norm to m31 is a square
+            let mut norm = a;
+            let mut conjugate = a;
+            for _ in 0..3 {
+                conjugate = conjugate.exp_u64(order);
+                norm *= conjugate;
+            }
+            let norm: &[Complex<p3_mersenne_31::Mersenne31>] = norm.as_base_slice();
+            let is_square =
+                norm[0].real().exp_u64((order - 1) / 2) == p3_mersenne_31::Mersenne31::one();
+
+            let a = QM31::from(a);
+            let b = QM31::from(b);
+
+            let script = script! {
+                { a }
+                qm31_is_square
+                { is_square as u32 }
+                OP_EQUALVERIFY
+                { b }
+                qm31_sqrt
+                qm31_square
+                { b }
+                qm31_equalverify
+                { b }
+                { a }
+                qm31_sqrt_with_hint
+                { a }
+                qm31_equalverify
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+
+            let script = script! {
+                { a }
+                qm31_sqrt
+                OP_2DROP OP_2DROP
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert_eq!(exec_result.success, is_square);
+        }
+    }
+
+    #[test]
+    fn test_qm31_batch_inverse() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!(
+            "qm31 batch inverse (n = 4): {}",
+            qm31_batch_inverse(4).len()
+        );
+        eprintln!(
+            "qm31 batch inverse with hint (n = 4): {}",
+            qm31_batch_inverse_with_hint(4).len()
+        );
+
+        for n in 1..5 {
+            let a: Vec<F> = (0..n).map(|_| rng.gen::<F>()).collect();
+            let a_inv: Vec<QM31> = a.iter().map(|v| QM31::from(v.inverse())).collect();
+            let a: Vec<QM31> = a.into_iter().map(QM31::from).collect();
+            let r = rng.gen::<QM31>();
+
+            let script = script! {
+                for v in a.iter() {
+                    { *v }
+                }
+                { qm31_batch_inverse(n) }
+                for v in a_inv.iter().rev() {
+                    { *v }
+                    qm31_equalverify
+                }
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+
+            let script = script! {
+                for v in a.iter() {
+                    { *v }
+                }
+                for v in a_inv.iter() {
+                    { *v }
+                }
+                { r }
+                { qm31_batch_inverse_with_hint(n) }
+                for v in a_inv.iter().rev() {
+                    { *v }
+                    qm31_equalverify
+                }
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+
+            // the elements themselves are not their inverses, so the product check fails
+            let script = script! {
+                for v in a.iter() {
+                    { *v }
+                }
+                for v in a.iter() {
+                    { *v }
+                }
+                { r }
+                { qm31_batch_inverse_with_hint(n) }
+                for _ in 0..n {
+                    OP_2DROP OP_2DROP
+                }
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(!exec_result.success);
+        }
+
+        // a hint with a limb shifted by p is rejected, even though it is the right inverse;
+        // the limbs of the inverses of 3 and of u that are zero can be shifted without overflow
+        let r = rng.gen::<QM31>();
+        for (x, j) in [
+            (QM31::new(3, 0, 0, 0), 0),
+            (QM31::new(3, 0, 0, 0), 1),
+            (QM31::new(3, 0, 0, 0), 2),
+            (QM31::new(0, 0, 1, 0), 3),
+        ] {
+            let mut hint = x.inverse().limbs();
+            assert_eq!(hint[j], 0);
+            hint[j] = MOD;
+
+            let script = script! {
+                { x }
+                for limb in hint {
+                    { limb }
+                }
+                { r }
+                { qm31_batch_inverse_with_hint(1) }
+                OP_2DROP OP_2DROP
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(!exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_qm31_horner_eval() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!("qm31 horner_eval (degree 4): {}", qm31_horner_eval(4).len());
+
+        for degree in 0..5 {
+            let coeffs: Vec<F> = (0..=degree).map(|_| rng.gen::<F>()).collect();
+            let z = rng.gen::<F>();
+            let value = coeffs.iter().rev().fold(F::zero(), |acc, c| acc * z + *c);
+
+            let coeffs: Vec<QM31> = coeffs.into_iter().map(QM31::from).collect();
+            let z = QM31::from(z);
+            let value = QM31::from(value);
+
+            let const_script = qm31_horner_eval_const(&coeffs);
+            eprintln!(
+                "qm31 horner_eval_const (degree {}): {}",
+                degree,
+                const_script.len()
+            );
+
+            let script = script! {
+                for c in coeffs.iter() {
+                    { *c }
+                }
+                { z }
+                { qm31_horner_eval(degree) }
+                { value }
+                qm31_equalverify
+                { z }
+                { const_script.clone() }
+                { value }
+                qm31_equalverify
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_qm31_native() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+
+        for _ in 0..20 {
+            let a = rng.gen::<F>();
+            let b = rng.gen::<F>();
+
+            let a_native = QM31::from(a);
+            let b_native = QM31::from(b);
+
+            assert_eq!(QM31::from(a.add(b)), a_native + b_native);
+            assert_eq!(QM31::from(a.sub(b)), a_native - b_native);
+            assert_eq!(QM31::from(a.mul(b)), a_native * b_native);
+            assert_eq!(QM31::from(a.neg()), -a_native);
+            assert_eq!(QM31::from(a.inverse()), a_native.inverse());
+            assert_eq!(QM31::from_limbs(a_native.limbs()), a_native);
+
+            let script = script! {
+                { a_native }
+                { b_native }
+                qm31_mul
+                { a_native * b_native }
+                qm31_equalverify
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_qm31_copy() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+
+        let a = rng.gen::<QM31>();
+        let b = rng.gen::<QM31>();
 
         let copy_script = qm31_copy(1);
 
         let script = script! {
-            { a[1].imag().as_canonical_u32() }
-            { a[1].real().as_canonical_u32() }
-            { a[0].imag().as_canonical_u32() }
-            { a[0].real().as_canonical_u32() }
-            { b[1].imag().as_canonical_u32() }
-            { b[1].real().as_canonical_u32() }
-            { b[0].imag().as_canonical_u32() }
-            { b[0].real().as_canonical_u32() }
+            { a }
+            { b }
             { copy_script.clone() }
-            { a[1].imag().as_canonical_u32() }
-            { a[1].real().as_canonical_u32() }
-            { a[0].imag().as_canonical_u32() }
-            { a[0].real().as_canonical_u32() }
+            { a }
             qm31_equalverify
-            { b[1].imag().as_canonical_u32() }
-            { b[1].real().as_canonical_u32() }
-            { b[0].imag().as_canonical_u32() }
-            { b[0].real().as_canonical_u32() }
+            { b }
             qm31_equalverify
-            { a[1].imag().as_canonical_u32() }
-            { a[1].real().as_canonical_u32() }
-            { a[0].imag().as_canonical_u32() }
-            { a[0].real().as_canonical_u32() }
+            { a }
             qm31_equalverify
             OP_TRUE
         };
@@ -374,33 +1518,18 @@ mod test {
     fn test_qm31_roll() {
         let mut rng = ChaCha20Rng::seed_from_u64(0u64);
 
-        let a = rng.gen::<F>();
-        let b = rng.gen::<F>();
-
-        let a: &[Complex<p3_mersenne_31::Mersenne31>] = a.as_base_slice();
-        let b: &[Complex<p3_mersenne_31::Mersenne31>] = b.as_base_slice();
+        let a = rng.gen::<QM31>();
+        let b = rng.gen::<QM31>();
 
         let roll_script = qm31_roll(1);
 
         let script = script! {
-            { a[1].imag().as_canonical_u32() }
-            { a[1].real().as_canonical_u32() }
-            { a[0].imag().as_canonical_u32() }
-            { a[0].real().as_canonical_u32() }
-            { b[1].imag().as_canonical_u32() }
-            { b[1].real().as_canonical_u32() }
-            { b[0].imag().as_canonical_u32() }
-            { b[0].real().as_canonical_u32() }
+            { a }
+            { b }
             { roll_script.clone() }
-            { a[1].imag().as_canonical_u32() }
-            { a[1].real().as_canonical_u32() }
-            { a[0].imag().as_canonical_u32() }
-            { a[0].real().as_canonical_u32() }
+            { a }
             qm31_equalverify
-            { b[1].imag().as_canonical_u32() }
-            { b[1].real().as_canonical_u32() }
-            { b[0].imag().as_canonical_u32() }
-            { b[0].real().as_canonical_u32() }
+            { b }
             qm31_equalverify
             OP_TRUE
         };
@@ -408,4 +1537,139 @@ mod test {
         let exec_result = execute_script(script);
         assert!(exec_result.success);
     }
+
+    #[test]
+    fn test_qm31_inner_product() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+
+        for n in [1, 2, 10] {
+            let inner_product_script = qm31_inner_product(n);
+            eprintln!("qm31 inner_product({}): {}", n, inner_product_script.len());
+
+            let a: Vec<F> = (0..n).map(|_| rng.gen()).collect();
+            let b: Vec<F> = (0..n).map(|_| rng.gen()).collect();
+
+            let mut c = F::zero();
+            for (a, b) in a.iter().zip(b.iter()) {
+                c += *a * *b;
+            }
+
+            let script = script! {
+                for v in a.iter().chain(b.iter()) {
+                    { QM31::from(*v) }
+                }
+                { inner_product_script.clone() }
+                { QM31::from(c) }
+                qm31_equalverify
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_qm31_inverse() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!("qm31 inverse: {}", qm31_inverse().len());
+
+        let a = rng.gen::<F>();
+        let b = a.inverse();
+
+        let script = script! {
+            { QM31::from(a) }
+            qm31_inverse
+            { QM31::from(b) }
+            qm31_equalverify
+            OP_TRUE
+        };
+        let exec_result = execute_script(script);
+        assert!(exec_result.success);
+    }
+
+    #[test]
+    fn test_qm31_inverse_with_hint() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!("qm31 inverse with hint: {}", qm31_inverse_with_hint().len());
+
+        let a = rng.gen::<F>();
+        let b = a.inverse();
+        let c = b + F::one();
+
+        for (hint, expected) in [(b, true), (c, false)] {
+            let script = script! {
+                { QM31::from(a) }
+                { QM31::from(hint) }
+                qm31_inverse_with_hint
+                { QM31::from(hint) }
+                qm31_equalverify
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert_eq!(exec_result.success, expected);
+        }
+    }
+
+    #[test]
+    fn test_qm31_div() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!("qm31 div: {}", qm31_div().len());
+        eprintln!("qm31 div with hint: {}", qm31_div_with_hint().len());
+
+        let a = rng.gen::<F>();
+        let b = rng.gen::<F>();
+        let c = a * b.inverse();
+
+        let a = QM31::from(a);
+        let b = QM31::from(b);
+        let c = QM31::from(c);
+
+        let script = script! {
+            { a }
+            { b }
+            qm31_div
+            { c }
+            qm31_equalverify
+            OP_TRUE
+        };
+        let exec_result = execute_script(script);
+        assert!(exec_result.success);
+
+        let script = script! {
+            { a }
+            { b }
+            { c }
+            qm31_div_with_hint
+            { c }
+            qm31_equalverify
+            OP_TRUE
+        };
+        let exec_result = execute_script(script);
+        assert!(exec_result.success);
+    }
+
+    #[test]
+    fn test_qm31_square() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0u64);
+        eprintln!("qm31 square: {}", qm31_square().len());
+        eprintln!(
+            "qm31 copy mul: {}",
+            script! { { qm31_copy(0) } qm31_mul }.len()
+        );
+
+        for _ in 0..10 {
+            let a = rng.gen::<F>();
+            let c = a.square();
+
+            let script = script! {
+                { QM31::from(a) }
+                qm31_square
+                { QM31::from(c) }
+                qm31_equalverify
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
 }
@@ -0,0 +1,319 @@
+use crate::treepp::Script;
+use crate::witness::decode_script_num;
+use bitcoin::opcodes::all::*;
+use bitcoin::opcodes::Opcode;
+use bitcoin::script::Instruction;
+
+/// The declared effect of a script on the stacks: it consumes the top `inputs` items of the main
+/// stack and leaves `outputs` items in their place, and likewise for the altstack.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StackEffect {
+    pub inputs: usize,
+    pub outputs: usize,
+    pub alt_inputs: usize,
+    pub alt_outputs: usize,
+}
+
+impl StackEffect {
+    /// An effect that leaves the altstack as it found it.
+    pub const fn new(inputs: usize, outputs: usize) -> Self {
+        StackEffect {
+            inputs,
+            outputs,
+            alt_inputs: 0,
+            alt_outputs: 0,
+        }
+    }
+
+    pub const fn with_alt(self, alt_inputs: usize, alt_outputs: usize) -> Self {
+        StackEffect {
+            alt_inputs,
+            alt_outputs,
+            ..self
+        }
+    }
+
+    /// The effect of running a script with this effect, followed by one with the effect `next`.
+    pub fn then(self, next: StackEffect) -> Self {
+        // `next` consumes the outputs of `self` first, and then the items below its inputs
+        StackEffect {
+            inputs: self.inputs + next.inputs.saturating_sub(self.outputs),
+            outputs: next.outputs + self.outputs.saturating_sub(next.inputs),
+            alt_inputs: self.alt_inputs + next.alt_inputs.saturating_sub(self.alt_outputs),
+            alt_outputs: next.alt_outputs + self.alt_outputs.saturating_sub(next.alt_inputs),
+        }
+    }
+}
+
+impl From<StackUsage> for StackEffect {
+    fn from(usage: StackUsage) -> Self {
+        StackEffect {
+            inputs: usage.inputs,
+            outputs: usage.outputs,
+            alt_inputs: usage.alt_inputs,
+            alt_outputs: usage.alt_outputs,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StackEffectError {
+    /// The script cannot be simulated without the values on the stack, see `stack_usage`.
+    Unknown,
+    /// The simulated effect differs from the declared one.
+    Mismatch {
+        declared: StackEffect,
+        actual: StackEffect,
+    },
+}
+
+/// Checks that `script` has the stack effect `declared`, by simulating the number of items on the
+/// main stack and the altstack. This does not run the script, so it applies to any composition of
+/// gadgets before it is executed.
+pub fn check_stack_effect(script: &Script, declared: StackEffect) -> Result<(), StackEffectError> {
+    let actual = StackEffect::from(stack_usage(script).ok_or(StackEffectError::Unknown)?);
+    if actual == declared {
+        Ok(())
+    } else {
+        Err(StackEffectError::Mismatch { declared, actual })
+    }
+}
+
+/// Stack usage of a script, relative to the stack it starts from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StackUsage {
+    /// The number of items the script reaches into on the main stack.
+    pub inputs: usize,
+    /// The number of items left on the main stack in place of the inputs.
+    pub outputs: usize,
+    /// The number of items the script pops from the altstack.
+    pub alt_inputs: usize,
+    /// The number of items left on the altstack in place of the altstack inputs.
+    pub alt_outputs: usize,
+    /// The peak number of items on the main stack, counting the inputs.
+    pub max_main: usize,
+    /// The peak number of items on the altstack, counting the altstack inputs.
+    pub max_alt: usize,
+}
+
+pub(crate) fn small_int(op: Opcode) -> Option<i64> {
+    match op.to_u8() {
+        0x4f => Some(-1),
+        code @ 0x51..=0x60 => Some(code as i64 - 0x50),
+        _ => None,
+    }
+}
+
+// (items popped, items pushed) on the main stack, for the opcodes without further structure
+fn stack_effect(op: Opcode) -> Option<(usize, usize)> {
+    let effect = match op {
+        OP_NOP => (0, 0),
+        OP_VERIFY | OP_DROP => (1, 0),
+        OP_2DROP => (2, 0),
+        OP_DUP => (1, 2),
+        OP_2DUP => (2, 4),
+        OP_3DUP => (3, 6),
+        OP_OVER => (2, 3),
+        OP_2OVER => (4, 6),
+        OP_NIP => (2, 1),
+        OP_SWAP => (2, 2),
+        OP_2SWAP => (4, 4),
+        OP_ROT => (3, 3),
+        OP_2ROT => (6, 6),
+        OP_TUCK => (2, 3),
+        OP_DEPTH => (0, 1),
+        OP_SIZE => (1, 2),
+        OP_EQUAL => (2, 1),
+        OP_EQUALVERIFY => (2, 0),
+        OP_1ADD | OP_1SUB | OP_NEGATE | OP_ABS | OP_NOT | OP_0NOTEQUAL => (1, 1),
+        OP_ADD
+        | OP_SUB
+        | OP_MUL
+        | OP_DIV
+        | OP_BOOLAND
+        | OP_BOOLOR
+        | OP_NUMEQUAL
+        | OP_NUMNOTEQUAL
+        | OP_LESSTHAN
+        | OP_GREATERTHAN
+        | OP_LESSTHANOREQUAL
+        | OP_GREATERTHANOREQUAL
+        | OP_MIN
+        | OP_MAX => (2, 1),
+        OP_NUMEQUALVERIFY => (2, 0),
+        OP_WITHIN => (3, 1),
+        _ => return None,
+    };
+    Some(effect)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Depths {
+    main: isize,
+    alt: isize,
+}
+
+/// Derives the stack usage of a script by tracking the number of stack items over its opcodes.
+///
+/// Returns `None` if the script uses an opcode whose effect depends on the values on the stack
+/// (such as `OP_IFDUP`, or `OP_PICK` and `OP_ROLL` not directly preceded by a constant), or has
+/// branches that leave different numbers of items.
+pub fn stack_usage(script: &Script) -> Option<StackUsage> {
+    let mut depths = Depths { main: 0, alt: 0 };
+    let mut lowest = depths;
+    let mut highest = depths;
+    // the depths at each open `OP_IF` and, once `OP_ELSE` is seen, at the end of the first branch
+    let mut branches: Vec<(Depths, Option<Depths>)> = vec![];
+    let mut last_int = None;
+
+    macro_rules! take {
+        ($field:ident, $n:expr) => {{
+            depths.$field -= $n as isize;
+            lowest.$field = lowest.$field.min(depths.$field);
+        }};
+    }
+    macro_rules! put {
+        ($field:ident, $n:expr) => {{
+            depths.$field += $n as isize;
+            highest.$field = highest.$field.max(depths.$field);
+        }};
+    }
+
+    for instruction in script.instructions() {
+        let op = match instruction.ok()? {
+            Instruction::PushBytes(bytes) => {
+                put!(main, 1);
+                last_int = decode_script_num(bytes.as_bytes());
+                continue;
+            }
+            Instruction::Op(op) => op,
+        };
+
+        if let Some(v) = small_int(op) {
+            put!(main, 1);
+            last_int = Some(v);
+            continue;
+        }
+
+        match op {
+            OP_IF | OP_NOTIF => {
+                take!(main, 1);
+                branches.push((depths, None));
+            }
+            OP_ELSE => {
+                let (start, first) = branches.last_mut()?;
+                if first.is_some() {
+                    return None;
+                }
+                *first = Some(depths);
+                depths = *start;
+            }
+            OP_ENDIF => {
+                let (start, first) = branches.pop()?;
+                if depths != first.unwrap_or(start) {
+                    return None;
+                }
+            }
+            OP_TOALTSTACK => {
+                take!(main, 1);
+                put!(alt, 1);
+            }
+            OP_FROMALTSTACK => {
+                take!(alt, 1);
+                put!(main, 1);
+            }
+            OP_PICK | OP_ROLL => {
+                let n = usize::try_from(last_int?).ok()?;
+                take!(main, 1);
+                take!(main, n + 1);
+                put!(main, if op == OP_PICK { n + 2 } else { n + 1 });
+            }
+            _ => {
+                let (pops, pushes) = stack_effect(op)?;
+                take!(main, pops);
+                put!(main, pushes);
+            }
+        }
+        last_int = None;
+    }
+
+    if !branches.is_empty() {
+        return None;
+    }
+
+    let inputs = -lowest.main;
+    let alt_inputs = -lowest.alt;
+    Some(StackUsage {
+        inputs: inputs as usize,
+        outputs: (inputs + depths.main) as usize,
+        alt_inputs: alt_inputs as usize,
+        alt_outputs: (alt_inputs + depths.alt) as usize,
+        max_main: (inputs + highest.main) as usize,
+        max_alt: (alt_inputs + highest.alt) as usize,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::treepp::*;
+    use crate::{
+        check_stack_effect, cm31_fromaltstack, cm31_toaltstack, m31_add, qm31_add, qm31_copy,
+        qm31_mul, stack_usage, StackEffect, StackEffectError,
+    };
+
+    #[test]
+    fn test_stack_usage() {
+        let usage = stack_usage(&qm31_copy(1)).unwrap();
+        assert_eq!((usage.inputs, usage.outputs, usage.max_main), (8, 12, 12));
+
+        let usage = stack_usage(&cm31_fromaltstack()).unwrap();
+        assert_eq!((usage.alt_inputs, usage.alt_outputs), (2, 0));
+        assert_eq!((usage.inputs, usage.outputs), (0, 2));
+
+        // the depth of `OP_PICK` is only known when it is pushed right before
+        assert!(stack_usage(&script! { OP_PICK }).is_none());
+        // unbalanced branches
+        assert!(stack_usage(&script! { OP_IF OP_DUP OP_ENDIF }).is_none());
+        assert!(stack_usage(&script! { OP_IF OP_DUP OP_ELSE OP_DROP OP_ENDIF }).is_none());
+        assert!(stack_usage(&script! { OP_IF OP_DUP OP_ELSE 1 OP_ENDIF }).is_some());
+    }
+
+    #[test]
+    fn test_check_stack_effect() {
+        let add = StackEffect::new(2, 1);
+        assert_eq!(check_stack_effect(&m31_add(), add), Ok(()));
+        assert_eq!(
+            check_stack_effect(&m31_add(), StackEffect::new(3, 1)),
+            Err(StackEffectError::Mismatch {
+                declared: StackEffect::new(3, 1),
+                actual: add,
+            })
+        );
+        assert_eq!(
+            check_stack_effect(&script! { OP_ROLL }, add),
+            Err(StackEffectError::Unknown)
+        );
+
+        // (a * b) + c
+        let qm31_binary = StackEffect::new(8, 4);
+        let script = script! {
+            qm31_mul
+            qm31_add
+        };
+        assert_eq!(qm31_binary.then(qm31_binary), StackEffect::new(12, 4));
+        assert_eq!(
+            check_stack_effect(&script, qm31_binary.then(qm31_binary)),
+            Ok(())
+        );
+
+        // an altstack round trip is balanced
+        let to_alt = StackEffect::new(2, 0).with_alt(0, 2);
+        let from_alt = StackEffect::new(0, 2).with_alt(2, 0);
+        assert_eq!(to_alt.then(from_alt), StackEffect::new(2, 2));
+        let script = script! {
+            cm31_toaltstack
+            cm31_fromaltstack
+        };
+        assert_eq!(check_stack_effect(&script, to_alt.then(from_alt)), Ok(()));
+    }
+}
@@ -0,0 +1,815 @@
+use crate::treepp::*;
+
+// Gadgets shared by the 31-bit primes p = c * 2^16 + 1 with 2^14 <= c < 2^15, such as
+// BabyBear (c = 15 * 2^11) and KoalaBear (c = 127 * 2^8), and by their degree-4 extensions
+// F[x] / (x^4 - w).
+//
+// The modulus is passed as a parameter, and all elements are kept in [0, p).
+
+fn cofactor(p: u32) -> u32 {
+    let c = (p - 1) >> 16;
+    assert!(p & 0xffff == 1 && c >= 1 << 14 && c < 1 << 15);
+    c
+}
+
+// moves an element in (-p, p) into [0, p)
+fn adjust(p: u32) -> Script {
+    script! {
+        OP_DUP
+        0 OP_LESSTHAN
+        OP_IF { p } OP_ADD OP_ENDIF
+    }
+}
+
+// moves an element in (-p, p) into [-p, 0)
+fn adjust_negative(p: u32) -> Script {
+    script! {
+        OP_DUP
+        0 OP_GREATERTHANOREQUAL
+        OP_IF { p } OP_SUB OP_ENDIF
+    }
+}
+
+// Input: x in (-2^31, 2^31)
+// Output: a value in (-p, p) congruent to x * 2^16
+fn shift16(p: u32) -> Script {
+    // since c * 2^16 = -1 (mod p), writing x = q * c + r gives
+    //
+    // x * 2^16 = q * c * 2^16 + r * 2^16 = r * 2^16 - q
+    //
+    // where |r| < c so |r * 2^16| < p - 1, and |q| < 2^17 since c >= 2^14
+    let c = cofactor(p);
+    script! {
+        OP_DUP
+        { c } OP_DIV
+        OP_TUCK
+        { c } OP_MUL OP_SUB
+        { 1 << 16 } OP_MUL
+        OP_SWAP OP_SUB
+    }
+}
+
+// Input: x in [0, p)
+// Output: x_h x_l where x = x_h * 2^16 + x_l, x_h is in [0, c], and x_l is in [-2^15, 2^15)
+fn split() -> Script {
+    script! {
+        OP_DUP
+        { 1 << 15 } OP_ADD
+        { 1 << 16 } OP_DIV
+        OP_TUCK
+        { 1 << 16 } OP_MUL OP_SUB
+    }
+}
+
+pub(crate) fn add(p: u32) -> Script {
+    script! {
+        { p } OP_SUB
+        OP_ADD
+        { adjust(p) }
+    }
+}
+
+pub(crate) fn double(p: u32) -> Script {
+    script! {
+        OP_DUP
+        { add(p) }
+    }
+}
+
+pub(crate) fn sub(p: u32) -> Script {
+    script! {
+        OP_SUB
+        { adjust(p) }
+    }
+}
+
+pub(crate) fn neg(p: u32) -> Script {
+    // zero is kept as zero, so that the output is canonical
+    script! {
+        OP_DUP
+        OP_IF
+            { p } OP_SWAP OP_SUB
+        OP_ENDIF
+    }
+}
+
+pub(crate) fn mul(p: u32) -> Script {
+    script! {
+        // idea:
+        // - split a into a_h and a_l where a = a_h * 2^16 + a_l, a_h is in [0, c], and a_l is in [-2^15, 2^15)
+        // - split b into b_h and b_l in the same way
+        //
+        // a * b = a_h * b_h * 2^32 + (a_h * b_l + a_l * b_h) * 2^16 + a_l * b_l
+        //       = ((a_h * b_h) * 2^16 + (a_h * b_l + a_l * b_h)) * 2^16 + a_l * b_l
+        //
+        // here, a_h * b_h and |a_l * b_l| are both below 2^30, and |a_h * b_l| and |a_l * b_h| are at most
+        // c * 2^15, so that |a_h * b_l + a_l * b_h| is at most p - 1 and none of them overflows.
+        //
+        // the two multiplications by 2^16 are done with `shift16`.
+
+        OP_SWAP
+        split
+        OP_ROT
+        split
+        // current stack: a_h a_l b_h b_l
+
+        // compute a_l * b_l
+        2 OP_PICK
+        OP_OVER
+        OP_MUL OP_TOALTSTACK
+
+        // compute a_h * b_l + a_l * b_h
+        3 OP_PICK
+        OP_MUL
+        OP_ROT
+        2 OP_PICK
+        OP_MUL
+        OP_ADD
+        { adjust_negative(p) }
+        OP_TOALTSTACK
+
+        // compute a_h * b_h
+        OP_MUL
+
+        // stack: a_h * b_h
+        // altstack: a_l * b_l   a_h * b_l + a_l * b_h
+
+        { shift16(p) }
+        OP_FROMALTSTACK OP_ADD
+        { shift16(p) }
+        { adjust(p) }
+        OP_FROMALTSTACK
+        { adjust(p) }
+        { add(p) }
+    }
+}
+
+pub(crate) fn mul_by_small_constant(p: u32, c: u32) -> Script {
+    // idea:
+    // - let d = ceil(p / c) and e = c * d - p, which is below c
+    // - split a = k * d + r where k is below c and r is below d
+    //
+    // c * a = k * c * d + c * r = k * e + c * r (mod p)
+    //
+    // where c * r is below c * d = p + e, so that c * r - p + k * e is in [-p, c^2), and
+    // subtracting p before the sum keeps every intermediate value within 31 bits
+    assert!(c > 0 && c < 1 << 13);
+    let d = p.div_ceil(c);
+    let e = c * d - p;
+
+    script! {
+        OP_DUP
+        { d } OP_DIV
+        OP_TUCK
+        { d } OP_MUL OP_SUB
+        { c } OP_MUL
+        { p } OP_SUB
+        OP_SWAP
+        { e } OP_MUL
+        OP_ADD
+        { adjust(p) }
+    }
+}
+
+// An element a0 + a1 * x + a2 * x^2 + a3 * x^3 of the extension F[x] / (x^4 - w)
+// is kept on the stack as
+//
+//      a3 a2 a1 a0
+//
+// with a0 on top, in the same way as qm31.
+
+// Input: a1 a0 b1 b0
+// Output: c2 c1 c0 where (a0 + a1 * x) * (b0 + b1 * x) = c0 + c1 * x + c2 * x^2
+fn karatsuba_small(p: u32) -> Script {
+    script! {
+        // compute a1 * b1 and a0 * b0
+        3 OP_PICK 2 OP_PICK
+        { mul(p) } OP_TOALTSTACK
+        2 OP_PICK OP_OVER
+        { mul(p) } OP_TOALTSTACK
+
+        // compute (a0 + a1) * (b0 + b1)
+        { add(p) }
+        OP_ROT OP_ROT
+        { add(p) }
+        { mul(p) }
+
+        // c1 = (a0 + a1) * (b0 + b1) - a0 * b0 - a1 * b1
+        OP_FROMALTSTACK OP_FROMALTSTACK
+        OP_2DUP { add(p) }
+        3 OP_ROLL OP_SWAP { sub(p) }
+        OP_ROT
+    }
+}
+
+// multiplication in the subfield F[y] / (y^2 - w) where y = x^2
+//
+// Input: a1 a0 b1 b0
+// Output: c1 c0 where (a0 + a1 * y) * (b0 + b1 * y) = c0 + c1 * y
+fn ext2_mul(p: u32, w: u32) -> Script {
+    script! {
+        { karatsuba_small(p) }
+        OP_ROT
+        { mul_by_small_constant(p, w) }
+        { add(p) }
+    }
+}
+
+fn ext2_sub(p: u32) -> Script {
+    script! {
+        OP_ROT OP_SWAP { sub(p) } OP_TOALTSTACK
+        { sub(p) } OP_FROMALTSTACK
+    }
+}
+
+pub(crate) fn ext4_add(p: u32) -> Script {
+    script! {
+        for i in 0..3 {
+            { 4 - i } OP_ROLL
+            { add(p) }
+            OP_TOALTSTACK
+        }
+        { add(p) }
+        for _ in 0..3 {
+            OP_FROMALTSTACK
+        }
+    }
+}
+
+pub(crate) fn ext4_sub(p: u32) -> Script {
+    script! {
+        for i in 0..3 {
+            { 4 - i } OP_ROLL OP_SWAP
+            { sub(p) }
+            OP_TOALTSTACK
+        }
+        { sub(p) }
+        for _ in 0..3 {
+            OP_FROMALTSTACK
+        }
+    }
+}
+
+pub(crate) fn ext4_equalverify() -> Script {
+    script! {
+        for i in 0..3 {
+            { 4 - i } OP_ROLL
+            OP_EQUALVERIFY
+        }
+        OP_EQUALVERIFY
+    }
+}
+
+pub(crate) fn ext4_mul(p: u32, w: u32) -> Script {
+    // input stack: a3 a2 a1 a0 b3 b2 b1 b0
+    //
+    // idea:
+    // - write a = A0 + A1 * x^2 and b = B0 + B1 * x^2 where A0, A1, B0, B1 are linear in x
+    // - compute P = A0 * B0, Q = A1 * B1, and M = (A0 + A1) * (B0 + B1) with `karatsuba_small`
+    //
+    // a * b = P + (M - P - Q) * x^2 + Q * x^4
+    //
+    // with P = p0 + p1 * x + p2 * x^2 and likewise for Q and M, reducing x^4 = w gives
+    //
+    // r0 = p0 + w * (m2 - p2 - q2 + q0)
+    // r1 = p1 + w * q1
+    // r2 = p2 + m0 - p0 - q0 + w * q2
+    // r3 = m1 - p1 - q1
+    script! {
+        // compute Q
+        7 OP_PICK 7 OP_PICK 5 OP_PICK 5 OP_PICK
+        { karatsuba_small(p) }
+        OP_TOALTSTACK OP_TOALTSTACK OP_TOALTSTACK
+
+        // compute P
+        5 OP_PICK 5 OP_PICK 3 OP_PICK 3 OP_PICK
+        { karatsuba_small(p) }
+        OP_TOALTSTACK OP_TOALTSTACK OP_TOALTSTACK
+
+        // compute B0 + B1 and A0 + A1
+        OP_ROT { add(p) }
+        OP_ROT OP_ROT { add(p) }
+        OP_SWAP
+        OP_2SWAP
+        5 OP_ROLL
+        OP_ROT { add(p) }
+        OP_SWAP 4 OP_ROLL { add(p) }
+
+        // compute M
+        { karatsuba_small(p) }
+        OP_FROMALTSTACK OP_FROMALTSTACK OP_FROMALTSTACK
+        OP_FROMALTSTACK OP_FROMALTSTACK OP_FROMALTSTACK
+        // current stack: m2 m1 m0 p2 p1 p0 q2 q1 q0
+
+        // compute r0
+        8 OP_ROLL
+        6 OP_PICK { sub(p) }
+        3 OP_PICK { sub(p) }
+        1 OP_PICK { add(p) }
+        { mul_by_small_constant(p, w) }
+        4 OP_PICK { add(p) }
+        OP_TOALTSTACK
+
+        // compute r1
+        1 OP_PICK
+        { mul_by_small_constant(p, w) }
+        5 OP_PICK { add(p) }
+        OP_TOALTSTACK
+
+        // compute r2
+        2 OP_ROLL
+        { mul_by_small_constant(p, w) }
+        OP_SWAP { sub(p) }
+        2 OP_ROLL { sub(p) }
+        3 OP_ROLL { add(p) }
+        3 OP_ROLL { add(p) }
+        OP_TOALTSTACK
+
+        // compute r3
+        { add(p) }
+        { sub(p) }
+
+        OP_FROMALTSTACK OP_FROMALTSTACK OP_FROMALTSTACK
+    }
+}
+
+pub(crate) fn ext4_square(p: u32, w: u32) -> Script {
+    // input stack: a3 a2 a1 a0
+    //
+    // idea: reducing x^4 = w in the square gives
+    //
+    // r0 = a0^2 + w * (a2^2 + 2 * a1 * a3)
+    // r1 = 2 * (a0 * a1 + w * a2 * a3)
+    // r2 = a1^2 + 2 * a0 * a2 + w * a3^2
+    // r3 = 2 * (a0 + a1) * (a2 + a3) - 2 * a0 * a2 - 2 * a1 * a3
+    //
+    // which takes 9 multiplications, and 2 * a0 * a2 and 2 * a1 * a3 are shared
+    script! {
+        // compute t = 2 * a1 * a3 and s = 2 * a0 * a2
+        1 OP_PICK { double(p) }
+        4 OP_PICK { mul(p) }
+        1 OP_PICK { double(p) }
+        4 OP_PICK { mul(p) }
+        // current stack: a3 a2 a1 a0 t s
+
+        // compute r0
+        4 OP_PICK OP_DUP { mul(p) }
+        2 OP_PICK { add(p) }
+        { mul_by_small_constant(p, w) }
+        3 OP_PICK OP_DUP { mul(p) }
+        { add(p) }
+        OP_TOALTSTACK
+
+        // compute r1
+        5 OP_PICK 5 OP_PICK { mul(p) }
+        { mul_by_small_constant(p, w) }
+        3 OP_PICK 5 OP_PICK { mul(p) }
+        { add(p) }
+        { double(p) }
+        OP_TOALTSTACK
+
+        // compute r2
+        5 OP_PICK OP_DUP { mul(p) }
+        { mul_by_small_constant(p, w) }
+        1 OP_PICK { add(p) }
+        4 OP_PICK OP_DUP { mul(p) }
+        { add(p) }
+        OP_TOALTSTACK
+
+        // compute r3
+        { add(p) }
+        4 OP_ROLL 4 OP_ROLL { add(p) }
+        OP_2SWAP { add(p) }
+        { double(p) }
+        { mul(p) }
+        OP_SWAP { sub(p) }
+
+        OP_FROMALTSTACK OP_FROMALTSTACK OP_FROMALTSTACK
+    }
+}
+
+pub(crate) fn ext4_mul_base(p: u32) -> Script {
+    // input stack:
+    //
+    // extension element
+    // a3 a2 a1 a0
+    //
+    // base field element
+    // e
+    script! {
+        OP_DUP OP_DUP OP_DUP OP_TOALTSTACK OP_TOALTSTACK OP_TOALTSTACK
+
+        // a3
+        4 OP_ROLL
+        { mul(p) }
+
+        // a2
+        3 OP_ROLL OP_FROMALTSTACK
+        { mul(p) }
+
+        // a1
+        3 OP_ROLL OP_FROMALTSTACK
+        { mul(p) }
+
+        // a0
+        3 OP_ROLL OP_FROMALTSTACK
+        { mul(p) }
+    }
+}
+
+// `base_inverse` is the inverse gadget of the base field
+pub(crate) fn ext4_inverse(p: u32, w: u32, base_inverse: Script) -> Script {
+    // input stack: a3 a2 a1 a0
+    //
+    // idea:
+    // - write a = E + O * x where E = a0 + a2 * y and O = a1 + a3 * y lie in the subfield
+    //   F[y] / (y^2 - w) with y = x^2
+    // - a * (E - O * x) = E^2 - O^2 * y = N, which lies in the subfield
+    // - the inverse of N = n0 + n1 * y is (n0 - n1 * y) / (n0^2 - w * n1^2)
+    // - a^-1 = (E - O * x) * N^-1
+    //
+    // note: zero is mapped to zero
+    script! {
+        // current stack: a3 a1 a2 a0, i.e., O E
+        2 OP_ROLL OP_SWAP
+
+        // compute E^2 and O^2 * y
+        OP_2DUP OP_2DUP { ext2_mul(p, w) }
+        5 OP_PICK 5 OP_PICK OP_2DUP { ext2_mul(p, w) }
+        OP_SWAP { mul_by_small_constant(p, w) }
+
+        // compute N = E^2 - O^2 * y
+        { ext2_sub(p) }
+
+        // compute the inverse of n0^2 - w * n1^2
+        OP_2DUP
+        OP_DUP { mul(p) }
+        OP_SWAP OP_DUP { mul(p) }
+        { mul_by_small_constant(p, w) }
+        { sub(p) }
+        { base_inverse }
+
+        // compute N^-1 = v0 + v1 * y
+        OP_TUCK { mul(p) }
+        OP_ROT OP_ROT { mul(p) } { neg(p) }
+        OP_SWAP
+        // current stack: O E N^-1
+
+        // compute E * N^-1 and -O * N^-1
+        OP_2DUP
+        5 OP_ROLL 5 OP_ROLL
+        { ext2_mul(p, w) }
+        OP_TOALTSTACK OP_TOALTSTACK
+        { ext2_mul(p, w) }
+        { neg(p) } OP_SWAP { neg(p) } OP_SWAP
+        OP_FROMALTSTACK OP_FROMALTSTACK
+
+        // interleave into r3 r2 r1 r0
+        OP_ROT OP_SWAP
+    }
+}
+
+pub(crate) fn ext4_toaltstack() -> Script {
+    script! {
+        for _ in 0..4 {
+            OP_TOALTSTACK
+        }
+    }
+}
+
+pub(crate) fn ext4_fromaltstack() -> Script {
+    script! {
+        for _ in 0..4 {
+            OP_FROMALTSTACK
+        }
+    }
+}
+
+pub(crate) fn ext4_copy(offset: usize) -> Script {
+    let a = offset * 4 + 4 - 1;
+
+    script! {
+        for _ in 0..4 {
+            { a } OP_PICK
+        }
+    }
+}
+
+pub(crate) fn ext4_roll(offset: usize) -> Script {
+    let a = offset * 4 + 4 - 1;
+
+    script! {
+        for _ in 0..4 {
+            { a } OP_ROLL
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::babybear::{babybear_inverse, BABYBEAR_MOD};
+    use crate::babybear4::BABYBEAR4_NONRESIDUE;
+    use crate::exec::execute_script;
+    use crate::koalabear::{koalabear_inverse, KOALABEAR_MOD};
+    use crate::koalabear4::KOALABEAR4_NONRESIDUE;
+    use crate::treepp::*;
+    use p3_baby_bear::BabyBear;
+    use p3_field::extension::{BinomialExtensionField, BinomiallyExtendable};
+    use p3_field::{AbstractExtensionField, AbstractField, Field, PrimeField32};
+    use p3_koala_bear::KoalaBear;
+    use rand::distributions::{Distribution, Standard};
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+
+    use super::*;
+
+    // each test is generic over the p3 field F of modulus p, and runs for both BabyBear and
+    // KoalaBear
+
+    type Ext4<F> = BinomialExtensionField<F, 4>;
+
+    fn push_ext4<F: BinomiallyExtendable<4> + PrimeField32>(a: Ext4<F>) -> Script {
+        let coeffs = a.as_base_slice();
+        script! {
+            for i in (0..4).rev() {
+                { coeffs[i].as_canonical_u32() }
+            }
+        }
+    }
+
+    fn test_add_sub<F: PrimeField32>(p: u32)
+    where
+        Standard: Distribution<F>,
+    {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+
+        for _ in 0..100 {
+            let a: F = prng.gen();
+            let b: F = prng.gen();
+
+            let script = script! {
+                { a.as_canonical_u32() }
+                { b.as_canonical_u32() }
+                { add(p) }
+                { (a + b).as_canonical_u32() }
+                OP_EQUALVERIFY
+                { a.as_canonical_u32() }
+                { b.as_canonical_u32() }
+                { sub(p) }
+                { (a - b).as_canonical_u32() }
+                OP_EQUALVERIFY
+                { a.as_canonical_u32() }
+                { double(p) }
+                { a.double().as_canonical_u32() }
+                OP_EQUALVERIFY
+                { a.as_canonical_u32() }
+                { neg(p) }
+                { (-a).as_canonical_u32() }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+
+        let script = script! {
+            0
+            { neg(p) }
+            0
+            OP_EQUAL
+        };
+        let exec_result = execute_script(script);
+        assert!(exec_result.success);
+    }
+
+    fn test_mul<F: PrimeField32>(p: u32)
+    where
+        Standard: Distribution<F>,
+    {
+        let mut prng = ChaCha20Rng::seed_from_u64(6u64);
+
+        let edge = [
+            F::zero(),
+            F::one(),
+            -F::one(),
+            F::from_canonical_u32(1 << 15),
+            F::from_canonical_u32((cofactor(p) << 16) - (1 << 15)),
+            F::from_canonical_u32((1 << 15) - 1),
+        ];
+
+        let mut pairs = vec![];
+        for a in edge.iter() {
+            for b in edge.iter() {
+                pairs.push((*a, *b));
+            }
+        }
+        for _ in 0..100 {
+            pairs.push((prng.gen(), prng.gen()));
+        }
+
+        for (a, b) in pairs {
+            let script = script! {
+                { a.as_canonical_u32() }
+                { b.as_canonical_u32() }
+                { mul(p) }
+                { (a * b).as_canonical_u32() }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    fn test_mul_by_small_constant<F: PrimeField32>(p: u32)
+    where
+        Standard: Distribution<F>,
+    {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+
+        // 4114 with a close to p used to overflow for KoalaBear before p was subtracted first
+        for c in [1, 2, 3, 11, 31, 4114, (1 << 13) - 1] {
+            let mut elements = vec![F::zero(), -F::one(), F::from_canonical_u32(p - 513813)];
+            for _ in 0..20 {
+                elements.push(prng.gen());
+            }
+
+            for a in elements {
+                let script = script! {
+                    { a.as_canonical_u32() }
+                    { mul_by_small_constant(p, c) }
+                    { (a * F::from_canonical_u32(c)).as_canonical_u32() }
+                    OP_EQUAL
+                };
+                let exec_result = execute_script(script);
+                assert!(exec_result.success);
+            }
+        }
+    }
+
+    fn test_inverse<F: PrimeField32>(inverse: Script)
+    where
+        Standard: Distribution<F>,
+    {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+
+        for _ in 0..20 {
+            let a: F = prng.gen();
+
+            let script = script! {
+                { a.as_canonical_u32() }
+                { inverse.clone() }
+                { a.inverse().as_canonical_u32() }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+
+        let script = script! {
+            0
+            { inverse }
+            0
+            OP_EQUAL
+        };
+        let exec_result = execute_script(script);
+        assert!(exec_result.success);
+    }
+
+    fn test_ext4_add_sub_mul<F: BinomiallyExtendable<4> + PrimeField32>(p: u32, w: u32)
+    where
+        Standard: Distribution<F> + Distribution<Ext4<F>>,
+    {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+
+        for _ in 0..100 {
+            let a: Ext4<F> = prng.gen();
+            let b: Ext4<F> = prng.gen();
+            let c: F = prng.gen();
+
+            let script = script! {
+                { push_ext4(a) }
+                { push_ext4(b) }
+                { ext4_add(p) }
+                { push_ext4(a + b) }
+                ext4_equalverify
+                { push_ext4(a) }
+                { push_ext4(b) }
+                { ext4_sub(p) }
+                { push_ext4(a - b) }
+                ext4_equalverify
+                { push_ext4(a) }
+                { push_ext4(b) }
+                { ext4_mul(p, w) }
+                { push_ext4(a * b) }
+                ext4_equalverify
+                { push_ext4(a) }
+                { ext4_square(p, w) }
+                { push_ext4(a.square()) }
+                ext4_equalverify
+                { push_ext4(a) }
+                { c.as_canonical_u32() }
+                { ext4_mul_base(p) }
+                { push_ext4(a * c) }
+                ext4_equalverify
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    fn test_ext4_inverse<F: BinomiallyExtendable<4> + PrimeField32>(
+        p: u32,
+        w: u32,
+        base_inverse: Script,
+    ) where
+        Standard: Distribution<Ext4<F>>,
+    {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+        let inverse = ext4_inverse(p, w, base_inverse);
+
+        let mut elements = vec![
+            Ext4::<F>::one(),
+            Ext4::<F>::from_base(F::from_canonical_u32(w)),
+            Ext4::<F>::from_base_slice(&[F::zero(), F::one(), F::zero(), F::zero()]),
+        ];
+        for _ in 0..20 {
+            elements.push(prng.gen());
+        }
+
+        for a in elements {
+            let script = script! {
+                { push_ext4(a) }
+                { inverse.clone() }
+                { push_ext4(a.inverse()) }
+                ext4_equalverify
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+
+        let script = script! {
+            { push_ext4(Ext4::<F>::zero()) }
+            { inverse }
+            { push_ext4(Ext4::<F>::zero()) }
+            ext4_equalverify
+            OP_TRUE
+        };
+        let exec_result = execute_script(script);
+        assert!(exec_result.success);
+    }
+
+    #[test]
+    fn test_babybear() {
+        test_add_sub::<BabyBear>(BABYBEAR_MOD);
+        test_mul::<BabyBear>(BABYBEAR_MOD);
+        test_mul_by_small_constant::<BabyBear>(BABYBEAR_MOD);
+        test_inverse::<BabyBear>(babybear_inverse());
+    }
+
+    #[test]
+    fn test_koalabear() {
+        test_add_sub::<KoalaBear>(KOALABEAR_MOD);
+        test_mul::<KoalaBear>(KOALABEAR_MOD);
+        test_mul_by_small_constant::<KoalaBear>(KOALABEAR_MOD);
+        test_inverse::<KoalaBear>(koalabear_inverse());
+    }
+
+    #[test]
+    fn test_babybear4() {
+        test_ext4_add_sub_mul::<BabyBear>(BABYBEAR_MOD, BABYBEAR4_NONRESIDUE);
+        test_ext4_inverse::<BabyBear>(BABYBEAR_MOD, BABYBEAR4_NONRESIDUE, babybear_inverse());
+    }
+
+    #[test]
+    fn test_koalabear4() {
+        test_ext4_add_sub_mul::<KoalaBear>(KOALABEAR_MOD, KOALABEAR4_NONRESIDUE);
+        test_ext4_inverse::<KoalaBear>(KOALABEAR_MOD, KOALABEAR4_NONRESIDUE, koalabear_inverse());
+    }
+
+    #[test]
+    fn test_ext4_copy_roll() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+
+        let a: Ext4<BabyBear> = prng.gen();
+        let b: Ext4<BabyBear> = prng.gen();
+
+        let script = script! {
+            { push_ext4(a) }
+            { push_ext4(b) }
+            { ext4_copy(1) }
+            { push_ext4(a) }
+            ext4_equalverify
+            { ext4_roll(1) }
+            ext4_toaltstack
+            { push_ext4(b) }
+            ext4_equalverify
+            ext4_fromaltstack
+            { push_ext4(a) }
+            ext4_equalverify
+            OP_TRUE
+        };
+        let exec_result = execute_script(script);
+        assert!(exec_result.success);
+    }
+}
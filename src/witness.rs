@@ -0,0 +1,240 @@
+use crate::cm31::CM31;
+use crate::m31::{M31, MOD};
+use crate::prime_field31::PrimeField31;
+use crate::qm31::QM31;
+
+// Witness items are listed from the bottom of the stack to the top, i.e., in the order
+// in which they would be pushed, so an element is encoded in the same order as its
+// `Pushable` implementation pushes it.
+
+/// Encodes `v` as a minimally-encoded script number.
+pub fn encode_script_num(v: i64) -> Vec<u8> {
+    if v == 0 {
+        return vec![];
+    }
+
+    let negative = v < 0;
+    let mut abs = v.unsigned_abs();
+
+    let mut bytes = vec![];
+    while abs > 0 {
+        bytes.push((abs & 0xff) as u8);
+        abs >>= 8;
+    }
+
+    // the most significant bit of the last byte is the sign bit
+    if bytes.last().unwrap() & 0x80 != 0 {
+        bytes.push(if negative { 0x80 } else { 0 });
+    } else if negative {
+        *bytes.last_mut().unwrap() |= 0x80;
+    }
+
+    bytes
+}
+
+/// Decodes a minimally-encoded script number of at most 4 bytes, as accepted by the
+/// arithmetic opcodes.
+pub fn decode_script_num(bytes: &[u8]) -> Option<i64> {
+    if bytes.is_empty() {
+        return Some(0);
+    }
+    if bytes.len() > 4 {
+        return None;
+    }
+
+    // the last byte may only be 0x00 or 0x80 if the sign bit is needed
+    let last = bytes[bytes.len() - 1];
+    if last & 0x7f == 0 && (bytes.len() == 1 || bytes[bytes.len() - 2] & 0x80 == 0) {
+        return None;
+    }
+
+    let mut v = 0i64;
+    for (i, byte) in bytes.iter().enumerate() {
+        v |= (*byte as i64) << (8 * i);
+    }
+
+    if last & 0x80 != 0 {
+        v &= !(0x80i64 << (8 * (bytes.len() - 1)));
+        Some(-v)
+    } else {
+        Some(v)
+    }
+}
+
+/// A value that takes a fixed number of stack elements.
+pub trait Witness: Sized {
+    /// The number of stack elements.
+    const N_ELEMENTS: usize;
+
+    /// The witness items, from the bottom of the stack to the top.
+    fn to_witness(&self) -> Vec<Vec<u8>>;
+
+    /// Decodes exactly `N_ELEMENTS` items, failing on non-minimal encodings or values
+    /// outside of the field.
+    fn from_witness(items: &[Vec<u8>]) -> Option<Self>;
+}
+
+impl Witness for M31 {
+    const N_ELEMENTS: usize = 1;
+
+    fn to_witness(&self) -> Vec<Vec<u8>> {
+        vec![encode_script_num(self.value() as i64)]
+    }
+
+    fn from_witness(items: &[Vec<u8>]) -> Option<M31> {
+        if items.len() != 1 {
+            return None;
+        }
+
+        let v = decode_script_num(&items[0])?;
+        if (0..MOD as i64).contains(&v) {
+            Some(M31::new(v as u32))
+        } else {
+            None
+        }
+    }
+}
+
+impl Witness for CM31 {
+    const N_ELEMENTS: usize = 2;
+
+    fn to_witness(&self) -> Vec<Vec<u8>> {
+        let mut items = self.imag().to_witness();
+        items.extend(self.real().to_witness());
+        items
+    }
+
+    fn from_witness(items: &[Vec<u8>]) -> Option<CM31> {
+        if items.len() != 2 {
+            return None;
+        }
+
+        let imag = M31::from_witness(&items[0..1])?;
+        let real = M31::from_witness(&items[1..2])?;
+        Some(CM31(real, imag))
+    }
+}
+
+impl Witness for QM31 {
+    const N_ELEMENTS: usize = 4;
+
+    fn to_witness(&self) -> Vec<Vec<u8>> {
+        let mut items = self.1.to_witness();
+        items.extend(self.0.to_witness());
+        items
+    }
+
+    fn from_witness(items: &[Vec<u8>]) -> Option<QM31> {
+        if items.len() != 4 {
+            return None;
+        }
+
+        let x1 = CM31::from_witness(&items[0..2])?;
+        let x0 = CM31::from_witness(&items[2..4])?;
+        Some(QM31(x0, x1))
+    }
+}
+
+impl<const P: u32> Witness for PrimeField31<P> {
+    const N_ELEMENTS: usize = 1;
+
+    fn to_witness(&self) -> Vec<Vec<u8>> {
+        vec![encode_script_num(self.value() as i64)]
+    }
+
+    fn from_witness(items: &[Vec<u8>]) -> Option<Self> {
+        if items.len() != 1 {
+            return None;
+        }
+
+        let v = decode_script_num(&items[0])?;
+        if (0..P as i64).contains(&v) {
+            Some(Self::new(v as u32))
+        } else {
+            None
+        }
+    }
+}
+
+/// Encodes the values one after the other, so that the last value ends up on top of the stack.
+pub fn encode_witness<T: Witness>(values: &[T]) -> Vec<Vec<u8>> {
+    values.iter().flat_map(|v| v.to_witness()).collect()
+}
+
+/// Decodes a sequence of values, the inverse of `encode_witness`.
+pub fn decode_witness<T: Witness>(items: &[Vec<u8>]) -> Option<Vec<T>> {
+    if !items.len().is_multiple_of(T::N_ELEMENTS) {
+        return None;
+    }
+
+    items.chunks(T::N_ELEMENTS).map(T::from_witness).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::exec::execute_script_with_witness;
+    use crate::treepp::*;
+    use crate::{
+        decode_script_num, decode_witness, encode_script_num, encode_witness, qm31_mul, Witness,
+        CM31, M31, QM31,
+    };
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn test_script_num() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+
+        assert_eq!(encode_script_num(0), Vec::<u8>::new());
+        assert_eq!(encode_script_num(1), vec![1]);
+        assert_eq!(encode_script_num(-1), vec![0x81]);
+        assert_eq!(encode_script_num(0x80), vec![0x80, 0]);
+        assert_eq!(encode_script_num(-0x80), vec![0x80, 0x80]);
+        assert_eq!(
+            encode_script_num((1 << 31) - 2),
+            vec![0xfe, 0xff, 0xff, 0x7f]
+        );
+
+        assert_eq!(decode_script_num(&[0]), None);
+        assert_eq!(decode_script_num(&[1, 0]), None);
+        assert_eq!(decode_script_num(&[0x80]), None);
+        assert_eq!(decode_script_num(&[1, 2, 3, 4, 5]), None);
+
+        for _ in 0..100 {
+            let v = prng.gen_range(-(1i64 << 31) + 1..1i64 << 31);
+            assert_eq!(decode_script_num(&encode_script_num(v)), Some(v));
+        }
+    }
+
+    #[test]
+    fn test_witness_roundtrip() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+
+        let a: M31 = prng.gen();
+        let b: CM31 = prng.gen();
+        let c: Vec<QM31> = (0..5).map(|_| prng.gen()).collect();
+
+        assert_eq!(M31::from_witness(&a.to_witness()), Some(a));
+        assert_eq!(CM31::from_witness(&b.to_witness()), Some(b));
+        assert_eq!(decode_witness::<QM31>(&encode_witness(&c)), Some(c.clone()));
+
+        // values outside of the field are rejected
+        assert_eq!(M31::from_witness(&[encode_script_num(-1)]), None);
+        assert_eq!(M31::from_witness(&[encode_script_num((1 << 31) - 1)]), None);
+        assert_eq!(decode_witness::<QM31>(&encode_witness(&c)[1..]), None);
+    }
+
+    #[test]
+    fn test_witness_execution() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0u64);
+
+        let a: QM31 = prng.gen();
+        let b: QM31 = prng.gen();
+
+        let exec_result = execute_script_with_witness(qm31_mul(), encode_witness(&[a, b]));
+        assert_eq!(
+            decode_witness::<QM31>(&exec_result.final_stack),
+            Some(vec![a * b])
+        );
+    }
+}